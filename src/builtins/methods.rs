@@ -0,0 +1,326 @@
+use crate::interpreter::{Channel, LazyIterator, ScriptError, Value};
+use anyhow::{anyhow, Result};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+type Method = fn(&str, &[Value]) -> Result<Value>;
+
+/// Methods callable on strings, as `text.name(args)`.
+const STRING_METHODS: &[(&str, Method)] = &[
+    ("bytes", string_bytes),
+    ("chars", string_chars),
+    ("contains", string_contains),
+    ("ends_with", string_ends_with),
+    ("find", string_find),
+    ("graphemes", string_graphemes),
+    ("lines", string_lines),
+    ("lower", string_lower),
+    ("pad_left", string_pad_left),
+    ("pad_right", string_pad_right),
+    ("repeat", string_repeat),
+    ("replace", string_replace),
+    ("reverse", string_reverse),
+    ("split", string_split),
+    ("starts_with", string_starts_with),
+    ("trim", string_trim),
+    ("upper", string_upper),
+    ("width", string_width),
+];
+
+type IteratorMethod = fn(&LazyIterator, &[Value]) -> Result<Value>;
+
+/// Methods callable on iterators, such as generators and `read_lines(path)`.
+const ITERATOR_METHODS: &[(&str, IteratorMethod)] =
+    &[("collect", iterator_collect), ("next", iterator_next)];
+
+type ChannelMethod = fn(&Channel, &[Value]) -> Result<Value>;
+
+/// Methods callable on channels made by `channel()`.
+const CHANNEL_METHODS: &[(&str, ChannelMethod)] = &[
+    ("close", channel_close),
+    ("recv", channel_recv),
+    ("send", channel_send),
+];
+
+/// Calls `method` on `receiver`, dispatching on the receiver's type.
+pub fn call_method(receiver: &Value, method: &str, args: &[Value]) -> Result<Value> {
+    let found = match receiver {
+        Value::String(s) => STRING_METHODS
+            .iter()
+            .find(|(name, _)| *name == method)
+            .map(|(_, function)| function(s, args)),
+        Value::Iterator(iterator) => ITERATOR_METHODS
+            .iter()
+            .find(|(name, _)| *name == method)
+            .map(|(_, function)| function(iterator, args)),
+        Value::Channel(channel) => CHANNEL_METHODS
+            .iter()
+            .find(|(name, _)| *name == method)
+            .map(|(_, function)| function(channel, args)),
+        _ => None,
+    };
+    found.unwrap_or_else(|| {
+        Err(anyhow!(
+            "{} has no method '{}'",
+            receiver.type_name(),
+            method
+        ))
+    })
+}
+
+/// Lists the methods available on `value`, in alphabetical order.
+pub fn method_names(value: &Value) -> Vec<&'static str> {
+    match value {
+        Value::String(_) => STRING_METHODS.iter().map(|(name, _)| *name).collect(),
+        Value::Iterator(_) => ITERATOR_METHODS.iter().map(|(name, _)| *name).collect(),
+        Value::Channel(_) => CHANNEL_METHODS.iter().map(|(name, _)| *name).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn expect_args(method: &str, args: &[Value], count: usize) -> Result<()> {
+    if args.len() != count {
+        return Err(anyhow!(
+            "{} expects {} argument{}, got {}",
+            method,
+            count,
+            if count == 1 { "" } else { "s" },
+            args.len()
+        ));
+    }
+    Ok(())
+}
+
+fn string_arg<'a>(method: &str, args: &'a [Value], index: usize) -> Result<&'a str> {
+    match args.get(index) {
+        Some(Value::String(s)) => Ok(s),
+        Some(other) => Err(anyhow!(
+            "{} expects a string argument, got {}",
+            method,
+            other.type_name()
+        )),
+        None => Err(anyhow!("{} is missing argument {}", method, index + 1)),
+    }
+}
+
+fn count_arg(method: &str, args: &[Value], index: usize) -> Result<usize> {
+    match args.get(index) {
+        Some(Value::Integer(n)) => usize::try_from(*n)
+            .map_err(|_| anyhow!("{} expects a non-negative count, got {}", method, n)),
+        Some(other) => Err(anyhow!(
+            "{} expects an integer argument, got {}",
+            method,
+            other.type_name()
+        )),
+        None => Err(anyhow!("{} is missing argument {}", method, index + 1)),
+    }
+}
+
+/// Strings longer than this raise an `OverflowError` instead of exhausting
+/// memory, as `"ab".repeat(2 ** 62)` would.
+const MAX_STRING_BYTES: usize = 1 << 30;
+
+/// `s` repeated `count` times, refusing results over `MAX_STRING_BYTES`.
+pub fn repeat_string(s: &str, count: usize) -> Result<String> {
+    match s.len().checked_mul(count) {
+        Some(len) if len <= MAX_STRING_BYTES => Ok(s.repeat(count)),
+        _ => Err(ScriptError::new(
+            "OverflowError",
+            format!("String result is larger than {} bytes", MAX_STRING_BYTES),
+        )
+        .into()),
+    }
+}
+
+fn string_array<'a>(parts: impl Iterator<Item = &'a str>) -> Value {
+    Value::Array(parts.map(|part| Value::String(part.to_string())).collect())
+}
+
+fn string_trim(s: &str, args: &[Value]) -> Result<Value> {
+    expect_args("trim", args, 0)?;
+    Ok(Value::String(s.trim().to_string()))
+}
+
+fn string_lower(s: &str, args: &[Value]) -> Result<Value> {
+    expect_args("lower", args, 0)?;
+    Ok(Value::String(s.to_lowercase()))
+}
+
+fn string_upper(s: &str, args: &[Value]) -> Result<Value> {
+    expect_args("upper", args, 0)?;
+    Ok(Value::String(s.to_uppercase()))
+}
+
+/// `split()` splits on runs of whitespace; `split(sep)` on each `sep`.
+fn string_split(s: &str, args: &[Value]) -> Result<Value> {
+    match args {
+        [] => Ok(string_array(s.split_whitespace())),
+        [_] => {
+            let separator = string_arg("split", args, 0)?;
+            if separator.is_empty() {
+                return Err(anyhow!("split separator cannot be empty"));
+            }
+            Ok(string_array(s.split(separator)))
+        }
+        _ => Err(anyhow!(
+            "split expects 0 or 1 arguments, got {}",
+            args.len()
+        )),
+    }
+}
+
+fn string_replace(s: &str, args: &[Value]) -> Result<Value> {
+    expect_args("replace", args, 2)?;
+    let from = string_arg("replace", args, 0)?;
+    let to = string_arg("replace", args, 1)?;
+    Ok(Value::String(s.replace(from, to)))
+}
+
+fn string_starts_with(s: &str, args: &[Value]) -> Result<Value> {
+    expect_args("starts_with", args, 1)?;
+    let pattern = string_arg("starts_with", args, 0)?;
+    Ok(Value::Boolean(s.starts_with(pattern)))
+}
+
+fn string_ends_with(s: &str, args: &[Value]) -> Result<Value> {
+    expect_args("ends_with", args, 1)?;
+    let pattern = string_arg("ends_with", args, 0)?;
+    Ok(Value::Boolean(s.ends_with(pattern)))
+}
+
+fn string_contains(s: &str, args: &[Value]) -> Result<Value> {
+    expect_args("contains", args, 1)?;
+    let pattern = string_arg("contains", args, 0)?;
+    Ok(Value::Boolean(s.contains(pattern)))
+}
+
+fn string_lines(s: &str, args: &[Value]) -> Result<Value> {
+    expect_args("lines", args, 0)?;
+    Ok(string_array(s.lines()))
+}
+
+/// `pad_left(width)` or `pad_left(width, fill)`, padding with spaces by
+/// default. Widths are terminal columns, so CJK text and emoji count double;
+/// strings already at least `width` columns wide are unchanged.
+fn string_pad_left(s: &str, args: &[Value]) -> Result<Value> {
+    let padding = padding("pad_left", s, args)?;
+    Ok(Value::String(padding + s))
+}
+
+fn string_pad_right(s: &str, args: &[Value]) -> Result<Value> {
+    let padding = padding("pad_right", s, args)?;
+    Ok(Value::String(s.to_string() + &padding))
+}
+
+fn padding(method: &str, s: &str, args: &[Value]) -> Result<String> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(anyhow!(
+            "{} expects 1 or 2 arguments, got {}",
+            method,
+            args.len()
+        ));
+    }
+    let width = count_arg(method, args, 0)?;
+    let fill = if args.len() == 2 {
+        let fill = string_arg(method, args, 1)?;
+        if fill.graphemes(true).count() != 1 || fill.width() == 0 {
+            return Err(anyhow!("{} fill must be a single character", method));
+        }
+        fill
+    } else {
+        " "
+    };
+    // A wide fill may leave one column short rather than overshoot
+    let missing = width.saturating_sub(s.width()) / fill.width();
+    repeat_string(fill, missing)
+}
+
+fn string_repeat(s: &str, args: &[Value]) -> Result<Value> {
+    expect_args("repeat", args, 1)?;
+    let count = count_arg("repeat", args, 0)?;
+    Ok(Value::String(repeat_string(s, count)?))
+}
+
+/// Returns the grapheme index of the first occurrence, or `nil`.
+fn string_find(s: &str, args: &[Value]) -> Result<Value> {
+    expect_args("find", args, 1)?;
+    let needle = string_arg("find", args, 0)?;
+    Ok(match s.find(needle) {
+        Some(byte_index) => Value::Integer(s[..byte_index].graphemes(true).count() as i64),
+        None => Value::Nil,
+    })
+}
+
+/// Reverses the grapheme clusters, keeping combining marks and emoji
+/// sequences intact.
+fn string_reverse(s: &str, args: &[Value]) -> Result<Value> {
+    expect_args("reverse", args, 0)?;
+    Ok(Value::String(s.graphemes(true).rev().collect()))
+}
+
+/// The UTF-8 encoding, as an array of byte values.
+fn string_bytes(s: &str, args: &[Value]) -> Result<Value> {
+    expect_args("bytes", args, 0)?;
+    Ok(Value::Array(
+        s.bytes().map(|b| Value::Integer(i64::from(b))).collect(),
+    ))
+}
+
+/// The Unicode code points, each as a one-character string.
+fn string_chars(s: &str, args: &[Value]) -> Result<Value> {
+    expect_args("chars", args, 0)?;
+    Ok(Value::Array(
+        s.chars().map(|c| Value::String(c.to_string())).collect(),
+    ))
+}
+
+/// The user-perceived characters that `len` counts and indexing returns.
+fn string_graphemes(s: &str, args: &[Value]) -> Result<Value> {
+    expect_args("graphemes", args, 0)?;
+    Ok(string_array(s.graphemes(true)))
+}
+
+/// The number of terminal columns the string occupies.
+fn string_width(s: &str, args: &[Value]) -> Result<Value> {
+    expect_args("width", args, 0)?;
+    Ok(Value::Integer(s.width() as i64))
+}
+
+/// Takes the next value as `Some(value)`, or `None` once exhausted.
+fn iterator_next(iterator: &LazyIterator, args: &[Value]) -> Result<Value> {
+    expect_args("next", args, 0)?;
+    Ok(match iterator.next_value().transpose()? {
+        Some(value) => Value::builtin_variant("Some", vec![value]),
+        None => Value::builtin_variant("None", vec![]),
+    })
+}
+
+/// Drains the remaining values into an array.
+fn iterator_collect(iterator: &LazyIterator, args: &[Value]) -> Result<Value> {
+    expect_args("collect", args, 0)?;
+    Ok(Value::Array(iterator.clone().collect::<Result<_>>()?))
+}
+
+/// Queues a value for a receiver; fails once the channel is closed.
+fn channel_send(channel: &Channel, args: &[Value]) -> Result<Value> {
+    expect_args("send", args, 1)?;
+    channel.send(args[0].clone())?;
+    Ok(Value::Nil)
+}
+
+/// Waits for the next value as `Some(value)`, or `None` once the channel is
+/// closed and drained.
+fn channel_recv(channel: &Channel, args: &[Value]) -> Result<Value> {
+    expect_args("recv", args, 0)?;
+    Ok(match channel.recv() {
+        Some(value) => Value::builtin_variant("Some", vec![value]),
+        None => Value::builtin_variant("None", vec![]),
+    })
+}
+
+/// Stops further sends; receivers still get the values already queued.
+fn channel_close(channel: &Channel, args: &[Value]) -> Result<Value> {
+    expect_args("close", args, 0)?;
+    channel.close();
+    Ok(Value::Nil)
+}
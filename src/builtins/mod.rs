@@ -1,15 +1,24 @@
-use crate::interpreter::{Environment, Value};
+mod methods;
+
+pub use methods::{call_method, repeat_string};
+
+use crate::interpreter::{
+    await_task, to_f64, Channel, Environment, LazyIterator, Range, ScriptError, Value,
+};
 use anyhow::{anyhow, Result};
+use num_bigint::BigInt;
 use std::fs;
 use std::io::{self, Write};
+use unicode_segmentation::UnicodeSegmentation;
 
-pub fn register_builtins(env: &mut Environment) {
+pub fn register_builtins(env: &Environment) {
     // IO functions
     env.define(
         "print".to_string(),
         Value::Function {
             name: "print".to_string(),
             parameters: vec![],
+            return_type: None,
             body: vec![],
             closure: Environment::new(),
         },
@@ -20,6 +29,7 @@ pub fn register_builtins(env: &mut Environment) {
         Value::Function {
             name: "println".to_string(),
             parameters: vec![],
+            return_type: None,
             body: vec![],
             closure: Environment::new(),
         },
@@ -30,6 +40,7 @@ pub fn register_builtins(env: &mut Environment) {
         Value::Function {
             name: "input".to_string(),
             parameters: vec![],
+            return_type: None,
             body: vec![],
             closure: Environment::new(),
         },
@@ -41,6 +52,18 @@ pub fn register_builtins(env: &mut Environment) {
         Value::Function {
             name: "read_file".to_string(),
             parameters: vec![],
+            return_type: None,
+            body: vec![],
+            closure: Environment::new(),
+        },
+    );
+
+    env.define(
+        "read_lines".to_string(),
+        Value::Function {
+            name: "read_lines".to_string(),
+            parameters: vec![],
+            return_type: None,
             body: vec![],
             closure: Environment::new(),
         },
@@ -51,6 +74,7 @@ pub fn register_builtins(env: &mut Environment) {
         Value::Function {
             name: "write_file".to_string(),
             parameters: vec![],
+            return_type: None,
             body: vec![],
             closure: Environment::new(),
         },
@@ -62,6 +86,7 @@ pub fn register_builtins(env: &mut Environment) {
         Value::Function {
             name: "len".to_string(),
             parameters: vec![],
+            return_type: None,
             body: vec![],
             closure: Environment::new(),
         },
@@ -72,6 +97,7 @@ pub fn register_builtins(env: &mut Environment) {
         Value::Function {
             name: "to_string".to_string(),
             parameters: vec![],
+            return_type: None,
             body: vec![],
             closure: Environment::new(),
         },
@@ -82,6 +108,7 @@ pub fn register_builtins(env: &mut Environment) {
         Value::Function {
             name: "to_int".to_string(),
             parameters: vec![],
+            return_type: None,
             body: vec![],
             closure: Environment::new(),
         },
@@ -92,6 +119,30 @@ pub fn register_builtins(env: &mut Environment) {
         Value::Function {
             name: "to_float".to_string(),
             parameters: vec![],
+            return_type: None,
+            body: vec![],
+            closure: Environment::new(),
+        },
+    );
+
+    env.define(
+        "range".to_string(),
+        Value::Function {
+            name: "range".to_string(),
+            parameters: vec![],
+            return_type: None,
+            body: vec![],
+            closure: Environment::new(),
+        },
+    );
+
+    // Introspection
+    env.define(
+        "methods".to_string(),
+        Value::Function {
+            name: "methods".to_string(),
+            parameters: vec![],
+            return_type: None,
             body: vec![],
             closure: Environment::new(),
         },
@@ -103,17 +154,56 @@ pub fn register_builtins(env: &mut Environment) {
         Value::Function {
             name: "exit".to_string(),
             parameters: vec![],
+            return_type: None,
+            body: vec![],
+            closure: Environment::new(),
+        },
+    );
+
+    // Error handling
+    env.define(
+        "error".to_string(),
+        Value::Function {
+            name: "error".to_string(),
+            parameters: vec![],
+            return_type: None,
+            body: vec![],
+            closure: Environment::new(),
+        },
+    );
+
+    env.define(
+        "throw".to_string(),
+        Value::Function {
+            name: "throw".to_string(),
+            parameters: vec![],
+            return_type: None,
             body: vec![],
             closure: Environment::new(),
         },
     );
 
+    // Result and Option constructors
+    for name in ["Ok", "Err", "Some", "None"] {
+        env.define(
+            name.to_string(),
+            Value::Function {
+                name: name.to_string(),
+                parameters: vec![],
+                return_type: None,
+                body: vec![],
+                closure: Environment::new(),
+            },
+        );
+    }
+
     // Shell built-ins
     env.define(
         "echo".to_string(),
         Value::Function {
             name: "echo".to_string(),
             parameters: vec![],
+            return_type: None,
             body: vec![],
             closure: Environment::new(),
         },
@@ -124,6 +214,7 @@ pub fn register_builtins(env: &mut Environment) {
         Value::Function {
             name: "cd".to_string(),
             parameters: vec![],
+            return_type: None,
             body: vec![],
             closure: Environment::new(),
         },
@@ -134,6 +225,7 @@ pub fn register_builtins(env: &mut Environment) {
         Value::Function {
             name: "pwd".to_string(),
             parameters: vec![],
+            return_type: None,
             body: vec![],
             closure: Environment::new(),
         },
@@ -144,6 +236,7 @@ pub fn register_builtins(env: &mut Environment) {
         Value::Function {
             name: "run".to_string(),
             parameters: vec![],
+            return_type: None,
             body: vec![],
             closure: Environment::new(),
         },
@@ -154,6 +247,29 @@ pub fn register_builtins(env: &mut Environment) {
         Value::Function {
             name: "show".to_string(),
             parameters: vec![],
+            return_type: None,
+            body: vec![],
+            closure: Environment::new(),
+        },
+    );
+
+    env.define(
+        "await_all".to_string(),
+        Value::Function {
+            name: "await_all".to_string(),
+            parameters: vec![],
+            return_type: None,
+            body: vec![],
+            closure: Environment::new(),
+        },
+    );
+
+    env.define(
+        "channel".to_string(),
+        Value::Function {
+            name: "channel".to_string(),
+            parameters: vec![],
+            return_type: None,
             body: vec![],
             closure: Environment::new(),
         },
@@ -227,14 +343,29 @@ pub fn call_builtin(name: &str, args: &[Value]) -> Result<Value> {
             Ok(Value::String(input))
         }
 
+        // Streams the file line by line, for files too large to read at once
+        "read_lines" => {
+            if args.len() != 1 {
+                return Err(anyhow!("read_lines expects 1 argument, got {}", args.len()));
+            }
+
+            let filename = args[0].to_string();
+            Ok(Value::Iterator(LazyIterator::file_lines(&filename)?))
+        }
+
         "read_file" => {
             if args.len() != 1 {
                 return Err(anyhow!("read_file expects 1 argument, got {}", args.len()));
             }
 
             let filename = args[0].to_string();
-            let content = fs::read_to_string(&filename)
-                .map_err(|e| anyhow!("Failed to read file {}: {}", filename, e))?;
+            let content = fs::read_to_string(&filename).map_err(|e| {
+                ScriptError::io(
+                    &e,
+                    format!("Failed to read file {}: {}", filename, e),
+                    &filename,
+                )
+            })?;
 
             Ok(Value::String(content))
         }
@@ -250,8 +381,13 @@ pub fn call_builtin(name: &str, args: &[Value]) -> Result<Value> {
             let filename = args[0].to_string();
             let content = args[1].to_string();
 
-            fs::write(&filename, content)
-                .map_err(|e| anyhow!("Failed to write file {}: {}", filename, e))?;
+            fs::write(&filename, content).map_err(|e| {
+                ScriptError::io(
+                    &e,
+                    format!("Failed to write file {}: {}", filename, e),
+                    &filename,
+                )
+            })?;
 
             Ok(Value::Nil)
         }
@@ -262,9 +398,11 @@ pub fn call_builtin(name: &str, args: &[Value]) -> Result<Value> {
             }
 
             let length = match &args[0] {
-                Value::String(s) => s.len() as i64,
-                Value::Array(arr) => arr.len() as i64,
-                Value::Map(map) => map.len() as i64,
+                Value::String(s) => s.graphemes(true).count() as i128,
+                Value::Array(arr) | Value::Tuple(arr) => arr.len() as i128,
+                Value::Map(map) => map.len() as i128,
+                // The widest ranges have more values than an i64 can count
+                Value::Range(range) => range.len(),
                 _ => {
                     return Err(anyhow!(
                         "len not supported for type {}",
@@ -273,7 +411,7 @@ pub fn call_builtin(name: &str, args: &[Value]) -> Result<Value> {
                 }
             };
 
-            Ok(Value::Integer(length))
+            Ok(BigInt::from(length).into())
         }
 
         "to_string" => {
@@ -290,13 +428,14 @@ pub fn call_builtin(name: &str, args: &[Value]) -> Result<Value> {
             }
 
             match &args[0] {
-                Value::Integer(i) => Ok(Value::Integer(*i)),
+                Value::Integer(_) | Value::BigInt(_) => Ok(args[0].clone()),
                 Value::Float(f) => Ok(Value::Integer(*f as i64)),
                 Value::String(s) => {
                     let parsed = s
-                        .parse::<i64>()
+                        .trim()
+                        .parse::<BigInt>()
                         .map_err(|e| anyhow!("Cannot convert '{}' to integer: {}", s, e))?;
-                    Ok(Value::Integer(parsed))
+                    Ok(Value::from(parsed))
                 }
                 Value::Boolean(true) => Ok(Value::Integer(1)),
                 Value::Boolean(false) => Ok(Value::Integer(0)),
@@ -312,6 +451,7 @@ pub fn call_builtin(name: &str, args: &[Value]) -> Result<Value> {
             match &args[0] {
                 Value::Float(f) => Ok(Value::Float(*f)),
                 Value::Integer(i) => Ok(Value::Float(*i as f64)),
+                Value::BigInt(i) => Ok(Value::Float(to_f64(i))),
                 Value::String(s) => {
                     let parsed = s
                         .parse::<f64>()
@@ -324,6 +464,49 @@ pub fn call_builtin(name: &str, args: &[Value]) -> Result<Value> {
             }
         }
 
+        "range" => {
+            let mut bounds = Vec::new();
+            for arg in args {
+                match arg {
+                    Value::Integer(i) => bounds.push(*i),
+                    Value::BigInt(i) => {
+                        return Err(anyhow!("range argument {} does not fit in 64 bits", i))
+                    }
+                    other => {
+                        return Err(anyhow!(
+                            "range expects integer arguments, got {}",
+                            other.type_name()
+                        ))
+                    }
+                }
+            }
+
+            let range = match bounds.as_slice() {
+                [end] => Range::new(0, *end, 1, false)?,
+                [start, end] => Range::new(*start, *end, 1, false)?,
+                [start, end, step] => Range::new(*start, *end, *step, false)?,
+                _ => {
+                    return Err(anyhow!(
+                        "range expects 1 to 3 arguments, got {}",
+                        args.len()
+                    ))
+                }
+            };
+            Ok(Value::Range(range))
+        }
+
+        "methods" => {
+            if args.len() != 1 {
+                return Err(anyhow!("methods expects 1 argument, got {}", args.len()));
+            }
+
+            let names = methods::method_names(&args[0])
+                .into_iter()
+                .map(|name| Value::String(name.to_string()))
+                .collect();
+            Ok(Value::Array(names))
+        }
+
         "exit" => {
             let code = if args.is_empty() {
                 0
@@ -406,22 +589,64 @@ pub fn call_builtin(name: &str, args: &[Value]) -> Result<Value> {
                     if output.status.success() {
                         Ok(Value::Nil)
                     } else {
-                        let exit_code = output.status.code().unwrap_or(-1);
-                        Err(anyhow!(
-                            "Command '{}' failed with exit code {}",
-                            command,
-                            exit_code
-                        ))
-                    }
-                }
-                Err(e) => {
-                    if e.kind() == std::io::ErrorKind::NotFound {
-                        Err(anyhow!("Command not found: {}", command))
-                    } else {
-                        Err(anyhow!("Failed to execute command '{}': {}", command, e))
+                        Err(ScriptError::command_failed(&command, output.status.code()).into())
                     }
                 }
+                Err(e) => Err(ScriptError::command_spawn(&command, &e).into()),
+            }
+        }
+
+        "error" => match args {
+            [message] => Ok(ScriptError::new("Error", message.to_string()).into_value()),
+            [kind, message] => {
+                Ok(ScriptError::new(&kind.to_string(), message.to_string()).into_value())
+            }
+            [kind, message, Value::Map(fields)] => {
+                let mut error = ScriptError::new(&kind.to_string(), message.to_string());
+                error.fields = fields.clone();
+                Ok(error.into_value())
+            }
+            _ => Err(anyhow!(
+                "error expects (message), (kind, message) or (kind, message, fields)"
+            )),
+        },
+
+        "Ok" | "Err" | "Some" => {
+            if args.len() != 1 {
+                return Err(anyhow!("{} expects 1 argument, got {}", name, args.len()));
+            }
+            Ok(Value::builtin_variant(name, args.to_vec()))
+        }
+
+        // A bare `None` is called with no arguments, like other built-ins
+        "None" => {
+            if !args.is_empty() {
+                return Err(anyhow!("None takes no arguments, got {}", args.len()));
+            }
+            Ok(Value::builtin_variant(name, Vec::new()))
+        }
+
+        "throw" => {
+            if args.len() != 1 {
+                return Err(anyhow!("throw expects 1 argument, got {}", args.len()));
+            }
+
+            Err(ScriptError::from_value(args[0].clone()).into())
+        }
+
+        // Waits for the tasks in order, raising the first error met
+        "await_all" => match args {
+            [Value::Array(tasks)] => Ok(Value::Array(
+                tasks.iter().map(await_task).collect::<Result<_>>()?,
+            )),
+            _ => Err(anyhow!("await_all expects an array of tasks")),
+        },
+
+        "channel" => {
+            if !args.is_empty() {
+                return Err(anyhow!("channel takes no arguments, got {}", args.len()));
             }
+            Ok(Value::Channel(Channel::new()))
         }
 
         _ => Err(anyhow!("Unknown built-in function: {}", name)),
@@ -435,16 +660,27 @@ pub fn is_builtin(name: &str) -> bool {
             | "println"
             | "input"
             | "read_file"
+            | "read_lines"
             | "write_file"
             | "len"
             | "to_string"
             | "to_int"
             | "to_float"
+            | "range"
+            | "methods"
             | "exit"
+            | "error"
+            | "throw"
+            | "Ok"
+            | "Err"
+            | "Some"
+            | "None"
             | "echo"
             | "cd"
             | "pwd"
             | "run"
             | "show"
+            | "await_all"
+            | "channel"
     )
 }
@@ -0,0 +1,1135 @@
+//! Static type checking for `shellux check`.
+//!
+//! The checker walks the AST once, inferring the types of local expressions
+//! and comparing them with the annotations on variables, parameters and return
+//! types. Anything it can't pin down is `any`, which is compatible with every
+//! type, so unannotated scripts always pass.
+
+use crate::builtins::is_builtin;
+use crate::parser::ast::*;
+use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("line {line}: {message}")]
+pub struct TypeError {
+    pub line: usize,
+    pub message: String,
+}
+
+/// A variable's type, and whether it came from an annotation. Only annotated
+/// variables are held to their type on reassignment.
+#[derive(Debug, Clone)]
+struct Binding {
+    ty: Type,
+    annotated: bool,
+}
+
+/// The function whose body is being checked.
+struct FunctionContext {
+    name: String,
+    return_type: Option<Type>,
+}
+
+struct Checker {
+    scopes: Vec<HashMap<String, Binding>>,
+    functions: Vec<FunctionContext>,
+    // Declared fields of each struct, by struct name
+    structs: HashMap<String, Vec<(String, Type)>>,
+    errors: Vec<TypeError>,
+}
+
+/// Checks `program` and returns every type error found, in source order.
+pub fn check(program: &Program) -> Vec<TypeError> {
+    let mut checker = Checker::new();
+    checker.check_block(&program.statements);
+    checker.errors
+}
+
+impl Checker {
+    fn new() -> Self {
+        Self {
+            scopes: vec![HashMap::new()],
+            functions: Vec::new(),
+            structs: HashMap::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    fn error(&mut self, line: usize, message: String) {
+        self.errors.push(TypeError { line, message });
+    }
+
+    fn declare(&mut self, name: &str, ty: Type, annotated: bool) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), Binding { ty, annotated });
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    fn lookup_mut(&mut self, name: &str) -> Option<&mut Binding> {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(name))
+    }
+
+    fn with_scope(&mut self, bindings: Vec<(String, Type)>, statements: &[Stmt]) {
+        self.scopes.push(HashMap::new());
+        for (name, ty) in bindings {
+            self.declare(&name, ty, false);
+        }
+        self.check_block(statements);
+        self.scopes.pop();
+    }
+
+    /// Checks a block used as a value and infers the type of its last
+    /// statement, which is the block's value.
+    fn block_type(&mut self, statements: &[Stmt]) -> Type {
+        self.scopes.push(HashMap::new());
+        let ty = match statements.split_last() {
+            Some((Stmt::Expression(last), rest)) => {
+                self.check_block(rest);
+                self.infer(last)
+            }
+            _ => {
+                self.check_block(statements);
+                Type::Any
+            }
+        };
+        self.scopes.pop();
+        ty
+    }
+
+    fn check_block(&mut self, statements: &[Stmt]) {
+        // Declare functions up front so calls may precede the definition
+        for stmt in statements {
+            let stmt = match stmt {
+                Stmt::Export(inner) => inner,
+                stmt => stmt,
+            };
+            match stmt {
+                Stmt::Function {
+                    name,
+                    parameters,
+                    return_type,
+                    ..
+                } => {
+                    let ty = function_type(parameters, return_type);
+                    self.declare(name, ty, true);
+                }
+                Stmt::Struct { name, fields, .. } => {
+                    self.structs.insert(name.clone(), fields.clone());
+                }
+                _ => {}
+            }
+        }
+
+        for stmt in statements {
+            self.check_statement(stmt);
+        }
+    }
+
+    fn check_statement(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expression(expr) => {
+                self.infer(expr);
+            }
+
+            Stmt::Let {
+                name,
+                type_annotation,
+                value,
+                line,
+            }
+            | Stmt::Const {
+                name,
+                type_annotation,
+                value,
+                line,
+            } => {
+                let actual = self.infer(value);
+                match type_annotation {
+                    Some(expected) => {
+                        if !is_assignable(expected, &actual) {
+                            self.error(
+                                *line,
+                                format!(
+                                    "cannot assign {} to '{}' of type {}",
+                                    actual, name, expected
+                                ),
+                            );
+                        }
+                        self.declare(name, expected.clone(), true);
+                    }
+                    None => self.declare(name, actual, false),
+                }
+            }
+
+            Stmt::Assignment {
+                target,
+                operator,
+                value,
+                line,
+            } => {
+                let actual = self.infer(value);
+                match target {
+                    AssignmentTarget::Identifier(name) => {
+                        if *operator != AssignmentOperator::Assign {
+                            return;
+                        }
+                        let mismatch = match self.lookup_mut(name) {
+                            Some(binding) if binding.annotated => {
+                                (!is_assignable(&binding.ty, &actual)).then(|| binding.ty.clone())
+                            }
+                            // An unannotated variable that changes type is
+                            // no longer tracked
+                            Some(binding) => {
+                                if binding.ty != actual {
+                                    binding.ty = Type::Any;
+                                }
+                                None
+                            }
+                            None => None,
+                        };
+                        if let Some(expected) = mismatch {
+                            self.error(
+                                *line,
+                                format!(
+                                    "cannot assign {} to '{}' of type {}",
+                                    actual, name, expected
+                                ),
+                            );
+                        }
+                    }
+                    AssignmentTarget::Index { object, index } => {
+                        self.infer(object);
+                        self.infer(index);
+                    }
+                    AssignmentTarget::FieldAccess { object, field } => {
+                        let object_type = self.infer(object);
+                        if *operator != AssignmentOperator::Assign {
+                            return;
+                        }
+                        let Type::Custom(struct_name) = &object_type else {
+                            return;
+                        };
+                        match self.field_type(struct_name, field) {
+                            Some(expected) if !is_assignable(&expected, &actual) => self.error(
+                                *line,
+                                format!(
+                                    "cannot assign {} to field '{}' of type {}",
+                                    actual, field, expected
+                                ),
+                            ),
+                            Some(_) => {}
+                            None if self.structs.contains_key(struct_name) => self
+                                .error(*line, format!("{} has no field '{}'", struct_name, field)),
+                            None => {}
+                        }
+                    }
+                }
+            }
+
+            // Structs are registered up front by `check_block`
+            Stmt::Struct { .. } => {}
+
+            Stmt::Enum { name, .. } => self.declare(name, Type::Any, false),
+
+            Stmt::Impl {
+                name: struct_name,
+                methods,
+                line,
+            } => {
+                if !self.structs.contains_key(struct_name) {
+                    self.error(
+                        *line,
+                        format!("cannot impl unknown struct '{}'", struct_name),
+                    );
+                }
+                for method in methods {
+                    if let Stmt::Function {
+                        name,
+                        parameters,
+                        return_type,
+                        body,
+                        ..
+                    } = method
+                    {
+                        // An unannotated `self` is the struct itself
+                        let parameters: Vec<Parameter> = parameters
+                            .iter()
+                            .map(|parameter| match parameter {
+                                Parameter {
+                                    name,
+                                    type_annotation: None,
+                                    ..
+                                } if name == "self" => Parameter {
+                                    type_annotation: Some(Type::Custom(struct_name.clone())),
+                                    ..parameter.clone()
+                                },
+                                parameter => parameter.clone(),
+                            })
+                            .collect();
+                        self.check_function(name, &parameters, return_type, body);
+                    }
+                }
+            }
+
+            Stmt::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                self.infer(condition);
+                self.with_scope(Vec::new(), then_block);
+                if let Some(else_block) = else_block {
+                    self.with_scope(Vec::new(), else_block);
+                }
+            }
+
+            Stmt::For {
+                variable,
+                iterable,
+                body,
+            } => {
+                let element = match self.infer(iterable) {
+                    Type::Array(element) => *element,
+                    Type::String => Type::String,
+                    Type::Map { key_type, .. } => *key_type,
+                    _ => Type::Any,
+                };
+                self.with_scope(vec![(variable.clone(), element)], body);
+            }
+
+            Stmt::While { condition, body } => {
+                self.infer(condition);
+                self.with_scope(Vec::new(), body);
+            }
+
+            Stmt::Function {
+                name,
+                parameters,
+                return_type,
+                body,
+                ..
+            } => self.check_function(name, parameters, return_type, body),
+
+            Stmt::Return { value, line } => {
+                let actual = value.as_ref().map(|value| self.infer(value));
+                let Some(FunctionContext {
+                    name,
+                    return_type: Some(expected),
+                }) = self.functions.last()
+                else {
+                    return;
+                };
+                let message = match actual {
+                    None if *expected != Type::Any => {
+                        format!("'{}' must return {} but returns nothing", name, expected)
+                    }
+                    Some(actual) if !is_assignable(expected, &actual) => {
+                        format!("'{}' must return {} but returns {}", name, expected, actual)
+                    }
+                    _ => return,
+                };
+                self.error(*line, message);
+            }
+
+            Stmt::Yield { value, .. } => {
+                self.infer(value);
+            }
+
+            Stmt::Try {
+                body,
+                catch_clauses,
+            } => {
+                self.with_scope(Vec::new(), body);
+                for clause in catch_clauses {
+                    let bindings = clause
+                        .variable
+                        .iter()
+                        .map(|name| (name.clone(), Type::Custom("error".to_string())))
+                        .collect();
+                    self.with_scope(bindings, &clause.body);
+                }
+            }
+
+            Stmt::Match { expr, arms } => {
+                self.infer(expr);
+                for arm in arms {
+                    self.scopes.push(HashMap::new());
+                    for name in pattern_names(&arm.pattern) {
+                        self.declare(&name, Type::Any, false);
+                    }
+                    if let Some(guard) = &arm.guard {
+                        self.infer(guard);
+                    }
+                    self.check_block(&arm.body);
+                    self.scopes.pop();
+                }
+            }
+
+            // Modules are not loaded here, so their exports are untyped
+            Stmt::Import {
+                path, alias, names, ..
+            } => {
+                if names.is_empty() {
+                    let name = alias.clone().or_else(|| {
+                        Path::new(path)
+                            .file_stem()
+                            .map(|stem| stem.to_string_lossy().into_owned())
+                    });
+                    if let Some(name) = name {
+                        self.declare(&name, Type::Any, false);
+                    }
+                } else {
+                    for name in names {
+                        self.declare(name, Type::Any, false);
+                    }
+                }
+            }
+
+            Stmt::Export(stmt) => self.check_statement(stmt),
+
+            Stmt::Destructure { pattern, value, .. } => {
+                let actual = self.infer(value);
+                // Element types are known when a tuple is unpacked name by name
+                let element_types = match (pattern, &actual) {
+                    (Pattern::Tuple(elements), Type::Tuple(types))
+                        if elements.len() == types.len() =>
+                    {
+                        Some(types.clone())
+                    }
+                    _ => None,
+                };
+                match (pattern, element_types) {
+                    (Pattern::Tuple(elements), Some(types)) => {
+                        for (element, ty) in elements.iter().zip(types) {
+                            if let Pattern::Identifier(name) = element {
+                                self.declare(name, ty, false);
+                            }
+                        }
+                    }
+                    (pattern, _) => {
+                        for name in pattern_names(pattern) {
+                            self.declare(&name, Type::Any, false);
+                        }
+                    }
+                }
+            }
+
+            Stmt::Break | Stmt::Continue => {}
+        }
+    }
+
+    /// The declared type of `field` on the struct `name`, if both are known.
+    fn field_type(&self, name: &str, field: &str) -> Option<Type> {
+        self.structs
+            .get(name)?
+            .iter()
+            .find(|(declared, _)| declared == field)
+            .map(|(_, ty)| ty.clone())
+    }
+
+    fn check_function(
+        &mut self,
+        name: &str,
+        parameters: &[Parameter],
+        return_type: &Option<Type>,
+        body: &[Stmt],
+    ) {
+        self.scopes.push(HashMap::new());
+        for parameter in parameters {
+            // Defaults can refer to the parameters before them
+            if let Some(default) = &parameter.default {
+                self.infer(default);
+            }
+            let annotated = parameter.type_annotation.is_some();
+            let mut ty = parameter.type_annotation.clone().unwrap_or(Type::Any);
+            if parameter.variadic {
+                ty = Type::Array(Box::new(ty));
+            }
+            self.declare(&parameter.name, ty, annotated);
+        }
+        self.functions.push(FunctionContext {
+            name: name.to_string(),
+            return_type: return_type.clone(),
+        });
+        self.check_block(body);
+        self.functions.pop();
+        self.scopes.pop();
+    }
+
+    /// Infers the type of `expr`, reporting errors found inside it.
+    fn infer(&mut self, expr: &Expr) -> Type {
+        match expr {
+            Expr::Integer(_) | Expr::BigInt(_) => Type::Int,
+            Expr::Float(_) => Type::Float,
+            Expr::String(_) | Expr::Command(_) => Type::String,
+            Expr::Boolean(_) => Type::Bool,
+            Expr::Nil => Type::Any,
+
+            Expr::Identifier(name) => self
+                .lookup(name)
+                .map_or(Type::Any, |binding| binding.ty.clone()),
+
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.infer(left);
+                let right = self.infer(right);
+                binary_result(operator, &left, &right)
+            }
+
+            Expr::Unary { operator, operand } => {
+                let operand = self.infer(operand);
+                match operator {
+                    UnaryOperator::Not => Type::Bool,
+                    UnaryOperator::Minus => match operand {
+                        Type::Int | Type::Float => operand,
+                        _ => Type::Any,
+                    },
+                    UnaryOperator::BitwiseNot => match operand {
+                        Type::Int => Type::Int,
+                        _ => Type::Any,
+                    },
+                }
+            }
+
+            Expr::Call { callee, args, line } => self.infer_call(callee, args, *line, None),
+
+            Expr::MethodCall { object, args, .. } => {
+                self.infer(object);
+                for arg in args {
+                    self.infer_argument(arg);
+                }
+                Type::Any
+            }
+
+            Expr::Lambda {
+                parameters,
+                return_type,
+                body,
+            } => {
+                self.check_function("<lambda>", parameters, return_type, body);
+                function_type(parameters, return_type)
+            }
+
+            Expr::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                self.infer(condition);
+                let then_type = self.block_type(then_block);
+                match else_block {
+                    Some(else_block) => {
+                        let else_type = self.block_type(else_block);
+                        common_type(vec![then_type, else_type])
+                    }
+                    None => Type::Any,
+                }
+            }
+
+            Expr::Block(statements) => self.block_type(statements),
+
+            Expr::Array(elements) => {
+                let types: Vec<Type> = elements.iter().map(|e| self.infer(e)).collect();
+                Type::Array(Box::new(common_type(types)))
+            }
+
+            Expr::Tuple(elements) => Type::Tuple(elements.iter().map(|e| self.infer(e)).collect()),
+
+            Expr::Map(pairs) => {
+                let mut value_types = Vec::new();
+                for (key, value) in pairs {
+                    self.infer(key);
+                    value_types.push(self.infer(value));
+                }
+                Type::Map {
+                    key_type: Box::new(Type::String),
+                    value_type: Box::new(common_type(value_types)),
+                }
+            }
+
+            Expr::Index { object, index } => {
+                let object = self.infer(object);
+                self.infer(index);
+                match object {
+                    // Indexing with a range slices
+                    Type::Array(_) if matches!(**index, Expr::Range { .. }) => object,
+                    Type::Array(element) => *element,
+                    Type::Map { value_type, .. } => *value_type,
+                    Type::String => Type::String,
+                    _ => Type::Any,
+                }
+            }
+
+            Expr::FieldAccess { object, field } => match self.infer(object) {
+                Type::Map { value_type, .. } => *value_type,
+                Type::Custom(name) => self.field_type(&name, field).unwrap_or(Type::Any),
+                _ => Type::Any,
+            },
+
+            Expr::Propagate(expr) => {
+                self.infer(expr);
+                Type::Any
+            }
+
+            Expr::Spawn(body) => {
+                self.block_type(body);
+                Type::Custom("task".to_string())
+            }
+
+            Expr::Await(task) => {
+                self.infer(task);
+                Type::Any
+            }
+
+            Expr::OptionalFieldAccess { object, .. } => {
+                self.infer(object);
+                Type::Any
+            }
+
+            Expr::StructLiteral { name, fields, line } => {
+                let Some(declared) = self.structs.get(name).cloned() else {
+                    self.error(*line, format!("unknown struct '{}'", name));
+                    for (_, value) in fields {
+                        self.infer(value);
+                    }
+                    return Type::Any;
+                };
+                for (field, value) in fields {
+                    let actual = self.infer(value);
+                    match declared.iter().find(|(declared, _)| declared == field) {
+                        Some((_, expected)) if !is_assignable(expected, &actual) => self.error(
+                            *line,
+                            format!(
+                                "field '{}' of {} must be {}, got {}",
+                                field, name, expected, actual
+                            ),
+                        ),
+                        Some(_) => {}
+                        None => self.error(*line, format!("{} has no field '{}'", name, field)),
+                    }
+                }
+                for (field, _) in &declared {
+                    if !fields.iter().any(|(given, _)| given == field) {
+                        self.error(*line, format!("missing field '{}' in {}", field, name));
+                    }
+                }
+                Type::Custom(name.clone())
+            }
+
+            Expr::Interpolation(parts) => {
+                for part in parts {
+                    if let InterpolationPart::Expression(expr) = part {
+                        self.infer(expr);
+                    }
+                }
+                Type::String
+            }
+
+            // `x |> f(a)` is checked as `f(x, a)`
+            Expr::Pipeline { left, right } => {
+                let input = self.infer(left);
+                match &**right {
+                    Expr::Call { callee, args, line } => {
+                        self.infer_call(callee, args, *line, Some(input))
+                    }
+                    right => {
+                        self.infer(right);
+                        Type::Any
+                    }
+                }
+            }
+
+            Expr::Range {
+                start, end, step, ..
+            } => {
+                self.infer(start);
+                self.infer(end);
+                if let Some(step) = step {
+                    self.infer(step);
+                }
+                Type::Any
+            }
+        }
+    }
+
+    fn infer_argument(&mut self, arg: &Argument) -> Type {
+        match arg {
+            Argument::Positional(expr) | Argument::Named(_, expr) => self.infer(expr),
+            Argument::Spread(expr) => {
+                self.infer(expr);
+                Type::Any
+            }
+        }
+    }
+
+    /// Infers the result of calling `callee`. A pipeline passes the type of
+    /// its input as `piped`, which goes before the written arguments.
+    fn infer_call(
+        &mut self,
+        callee: &Expr,
+        args: &[Argument],
+        line: usize,
+        piped: Option<Type>,
+    ) -> Type {
+        let arg_types: Vec<Type> = piped
+            .into_iter()
+            .chain(args.iter().map(|arg| self.infer_argument(arg)))
+            .collect();
+        match callee {
+            // Names in scope shadow built-ins, as they do at runtime
+            Expr::Identifier(name) if is_builtin(name) && self.lookup(name).is_none() => {
+                builtin_result(name)
+            }
+            callee => {
+                let callee_type = self.infer(callee);
+                // Named and spread arguments are matched up at runtime
+                if args
+                    .iter()
+                    .all(|arg| matches!(arg, Argument::Positional(_)))
+                {
+                    self.check_call(callee, &callee_type, &arg_types, line)
+                } else {
+                    match callee_type {
+                        Type::Function { return_type, .. } => *return_type,
+                        _ => Type::Any,
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_call(
+        &mut self,
+        callee: &Expr,
+        callee_type: &Type,
+        args: &[Type],
+        line: usize,
+    ) -> Type {
+        let describe = match callee {
+            Expr::Identifier(name) => format!("'{}'", name),
+            _ => "function".to_string(),
+        };
+        match callee_type {
+            Type::Function {
+                parameters,
+                return_type,
+            } => {
+                if parameters.len() != args.len() {
+                    self.error(
+                        line,
+                        format!(
+                            "{} expects {} argument{}, got {}",
+                            describe,
+                            parameters.len(),
+                            if parameters.len() == 1 { "" } else { "s" },
+                            args.len()
+                        ),
+                    );
+                } else {
+                    for (position, (expected, actual)) in parameters.iter().zip(args).enumerate() {
+                        if !is_assignable(expected, actual) {
+                            self.error(
+                                line,
+                                format!(
+                                    "argument {} to {} must be {}, got {}",
+                                    position + 1,
+                                    describe,
+                                    expected,
+                                    actual
+                                ),
+                            );
+                        }
+                    }
+                }
+                (**return_type).clone()
+            }
+            Type::Any | Type::Custom(_) => Type::Any,
+            other => {
+                self.error(line, format!("cannot call {} of type {}", describe, other));
+                Type::Any
+            }
+        }
+    }
+}
+
+/// Whether a value of type `actual` may be stored where `expected` is
+/// declared. Integers widen to floats.
+pub fn is_assignable(expected: &Type, actual: &Type) -> bool {
+    match (expected, actual) {
+        (Type::Any, _) | (_, Type::Any) => true,
+        (Type::Float, Type::Int) => true,
+        (Type::Array(expected), Type::Array(actual)) => is_assignable(expected, actual),
+        (Type::Tuple(expected), Type::Tuple(actual)) => {
+            expected.len() == actual.len()
+                && expected
+                    .iter()
+                    .zip(actual)
+                    .all(|(e, a)| is_assignable(e, a))
+        }
+        (
+            Type::Map {
+                key_type: expected_key,
+                value_type: expected_value,
+            },
+            Type::Map {
+                key_type: actual_key,
+                value_type: actual_value,
+            },
+        ) => is_assignable(expected_key, actual_key) && is_assignable(expected_value, actual_value),
+        (
+            Type::Function {
+                parameters: expected_parameters,
+                return_type: expected_return,
+            },
+            Type::Function {
+                parameters: actual_parameters,
+                return_type: actual_return,
+            },
+        ) => {
+            expected_parameters.len() == actual_parameters.len()
+                && expected_parameters
+                    .iter()
+                    .zip(actual_parameters)
+                    .all(|(e, a)| is_assignable(a, e))
+                && is_assignable(expected_return, actual_return)
+        }
+        (expected, actual) => expected == actual,
+    }
+}
+
+/// The type of a function, or `any` when defaults or a variadic parameter
+/// give it no fixed arity to check calls against.
+fn function_type(parameters: &[Parameter], return_type: &Option<Type>) -> Type {
+    if parameters.iter().any(|p| p.default.is_some() || p.variadic) {
+        return Type::Any;
+    }
+    Type::Function {
+        parameters: parameters
+            .iter()
+            .map(|p| p.type_annotation.clone().unwrap_or(Type::Any))
+            .collect(),
+        return_type: Box::new(return_type.clone().unwrap_or(Type::Any)),
+    }
+}
+
+/// The single type shared by all `types`, or `any` if they differ.
+fn common_type(types: Vec<Type>) -> Type {
+    let mut types = types.into_iter();
+    let Some(first) = types.next() else {
+        return Type::Any;
+    };
+    if types.all(|ty| ty == first) {
+        first
+    } else {
+        Type::Any
+    }
+}
+
+fn binary_result(operator: &BinaryOperator, left: &Type, right: &Type) -> Type {
+    use BinaryOperator::*;
+    match operator {
+        Equal | NotEqual | Less | LessEqual | Greater | GreaterEqual | In => Type::Bool,
+        // These return one of their operands
+        And | Or | NilCoalesce => common_type(vec![left.clone(), right.clone()]),
+        // A negative integer exponent gives a float
+        Power => match (left, right) {
+            (Type::Int, Type::Int) => Type::Any,
+            (Type::Int | Type::Float, Type::Int | Type::Float) => Type::Float,
+            _ => Type::Any,
+        },
+        Add | Subtract | Multiply | Divide | Modulo => match (left, right) {
+            (Type::Int, Type::Int) => Type::Int,
+            (Type::Int | Type::Float, Type::Int | Type::Float) => Type::Float,
+            (Type::String, Type::String) if *operator == Add => Type::String,
+            (Type::String, Type::Int) | (Type::Int, Type::String) if *operator == Multiply => {
+                Type::String
+            }
+            (Type::Array(_), Type::Array(_)) | (Type::Map { .. }, Type::Map { .. })
+                if *operator == Add && left == right =>
+            {
+                left.clone()
+            }
+            _ => Type::Any,
+        },
+        BitwiseAnd | BitwiseOr | BitwiseXor | LeftShift | RightShift => match (left, right) {
+            (Type::Int, Type::Int) => Type::Int,
+            _ => Type::Any,
+        },
+    }
+}
+
+fn builtin_result(name: &str) -> Type {
+    match name {
+        "len" | "to_int" => Type::Int,
+        "to_float" => Type::Float,
+        "to_string" | "input" | "read_file" => Type::String,
+        "read_lines" => Type::Custom("iterator".to_string()),
+        "channel" => Type::Custom("channel".to_string()),
+        "await_all" => Type::Array(Box::new(Type::Any)),
+        "error" => Type::Custom("error".to_string()),
+        "methods" => Type::Array(Box::new(Type::String)),
+        "Ok" | "Err" => Type::Custom("Result".to_string()),
+        "Some" | "None" => Type::Custom("Option".to_string()),
+        _ => Type::Any,
+    }
+}
+
+fn pattern_names(pattern: &Pattern) -> Vec<String> {
+    match pattern {
+        Pattern::Identifier(name) => vec![name.clone()],
+        Pattern::Rest(Some(name)) => vec![name.clone()],
+        Pattern::Array(elements) | Pattern::Tuple(elements) | Pattern::Or(elements) => {
+            elements.iter().flat_map(pattern_names).collect()
+        }
+        Pattern::Map(entries) => entries
+            .iter()
+            .flat_map(|(_, pattern)| pattern_names(pattern))
+            .collect(),
+        Pattern::Variant { fields, .. } => fields.iter().flat_map(pattern_names).collect(),
+        Pattern::Wildcard | Pattern::Literal(_) | Pattern::Range { .. } | Pattern::Rest(None) => {
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn check_source(source: &str) -> Vec<TypeError> {
+        let tokens = Lexer::new(source).tokenize();
+        let program = Parser::new(tokens).parse().unwrap();
+        check(&program)
+    }
+
+    #[test]
+    fn test_unannotated_code_passes() {
+        let source = r#"
+            let x is 1
+            x = "now a string"
+            fn add(a, b) {
+                return a + b
+            }
+            let f is n => n * 2
+            print(add(x, 2), f(3), len("abc"))
+        "#;
+        assert_eq!(check_source(source), vec![]);
+    }
+
+    #[test]
+    fn test_argument_and_variable_mismatches() {
+        let source = r#"
+fn square(n: int) -> int {
+    return n * n
+}
+let name is "bob"
+square(name)
+let count: int is "three"
+let ratio: float is 2
+let items: []string is ["a", "b"]
+let lookup: map[string]int is {"a": 1}
+count = 4.5
+"#;
+        let errors = check_source(source);
+        assert_eq!(
+            errors,
+            vec![
+                TypeError {
+                    line: 6,
+                    message: "argument 1 to 'square' must be int, got string".to_string(),
+                },
+                TypeError {
+                    line: 7,
+                    message: "cannot assign string to 'count' of type int".to_string(),
+                },
+                TypeError {
+                    line: 11,
+                    message: "cannot assign float to 'count' of type int".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_return_type_mismatches() {
+        let source = r#"
+fn label(n: int) -> string {
+    if n > 0 {
+        return n
+    }
+    return
+}
+fn apply(f: fn(int) -> int, x: int) -> int {
+    return f(x)
+}
+"#;
+        let errors = check_source(source);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].line, 4);
+        assert_eq!(
+            errors[0].message,
+            "'label' must return string but returns int"
+        );
+        assert_eq!(errors[1].line, 6);
+    }
+
+    #[test]
+    fn test_calling_non_functions() {
+        let source = "let n is 5\nn(1)\nfn two(a, b) {\n}\ntwo(1)";
+        let errors = check_source(source);
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec![
+                "line 2: cannot call 'n' of type int",
+                "line 5: 'two' expects 2 arguments, got 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tuple_returns() {
+        let source = r#"
+fn divide(a: float, b: float) -> (float, error) {
+    if b == 0 {
+        return 0.0, error("division by zero")
+    }
+    return a / b, nil
+}
+fn bad() -> (int, string) {
+    return "x", 1
+}
+result, err := divide(1.0, 2.0)
+let half: int is result
+"#;
+        let errors = check_source(source);
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec![
+                "line 9: 'bad' must return (int, string) but returns (string, int)",
+                "line 12: cannot assign float to 'half' of type int",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_if_expression_types() {
+        let source = r#"
+let label: string is if true { "yes" } else { "no" }
+let count: int is if true { 1 } else { "none" }
+let size: int is {
+    n := 2
+    n * 2
+}
+let bad: string is { 1 }
+"#;
+        let errors = check_source(source);
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec!["line 8: cannot assign int to 'bad' of type string"]
+        );
+    }
+
+    #[test]
+    fn test_struct_types() {
+        let source = r#"
+struct Server {
+    host: string,
+    port: int
+}
+impl Server {
+    fn url(self) -> string {
+        return self.port
+    }
+}
+let s: Server is Server { host: "db", port: "22" }
+t := Server { host: "db" }
+s.port = "80"
+let host: string is s.host
+"#;
+        let errors = check_source(source);
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec![
+                "line 8: 'url' must return string but returns int",
+                "line 11: field 'port' of Server must be int, got string",
+                "line 12: missing field 'port' in Server",
+                "line 13: cannot assign string to field 'port' of type int",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_result_and_option_types() {
+        let source = r#"
+fn parse(text: string) -> Result {
+    return Some(text)
+}
+fn twice(text: string) -> Result {
+    n := parse(text)?
+    return Ok(n)
+}
+match twice("1") {
+    Ok(value) => print(value)
+    Err(message) => print(message)
+}
+"#;
+        let errors = check_source(source);
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec!["line 3: 'parse' must return Result but returns Option"]
+        );
+    }
+
+    #[test]
+    fn test_flexible_signatures() {
+        let source = r#"
+fn deploy(env: string, dry_run: bool = false, ...targets: string) {
+    let names: string is targets
+}
+deploy("prod", dry_run: true)
+deploy("prod", true, "web", "db")
+fn two(a, b) {
+}
+two(...[1, 2])
+two(b: 1, a: 2)
+"#;
+        let errors = check_source(source);
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec!["line 3: cannot assign []string to 'names' of type string"]
+        );
+    }
+
+    #[test]
+    fn test_pipeline_input_is_first_argument() {
+        let source = r#"
+fn add(a: int, b: int) -> int {
+    return a + b
+}
+let x: int is 5 |> add(1)
+let y is "a" |> add(1)
+let z is 5 |> add(1, 2)
+"#;
+        let errors = check_source(source);
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec![
+                "line 6: argument 1 to 'add' must be int, got string",
+                "line 7: 'add' expects 2 arguments, got 3",
+            ]
+        );
+    }
+}
@@ -0,0 +1,259 @@
+use super::enums::unit_variant;
+use super::{Interpreter, Range, ScriptError, Value};
+use crate::parser::ast::{AssignmentOperator, AssignmentTarget, BinaryOperator, Expr};
+use anyhow::{anyhow, Result};
+use num_bigint::BigInt;
+use std::fmt;
+use unicode_segmentation::UnicodeSegmentation;
+
+impl Interpreter {
+    /// Performs `target op= value` and returns the stored value. Index and
+    /// field targets are updated in place inside the variable that holds them.
+    pub(super) fn assign(
+        &mut self,
+        target: AssignmentTarget,
+        operator: AssignmentOperator,
+        value: Value,
+    ) -> Result<Value> {
+        let (name, path) = match target {
+            AssignmentTarget::Identifier(name) => (name, Vec::new()),
+            AssignmentTarget::Index { object, index } => {
+                self.assignment_path(Expr::Index { object, index })?
+            }
+            AssignmentTarget::FieldAccess { object, field } => {
+                self.assignment_path(Expr::FieldAccess { object, field })?
+            }
+        };
+
+        let mut root = self
+            .environment
+            .get(&name)
+            .ok_or_else(|| anyhow!("Undefined variable: {}", name))?;
+
+        // Struct fields keep their declared types
+        let mut field_type = None;
+        let slot = match path.split_last() {
+            None => &mut root,
+            Some((last, parents)) => {
+                let mut container = &mut root;
+                for key in parents {
+                    container = element_mut(container, key, false)?;
+                }
+                if let (Value::Struct { definition, .. }, Value::String(field)) =
+                    (&*container, last)
+                {
+                    field_type = definition
+                        .field_index(field)
+                        .map(|index| (field.clone(), definition.clone(), index));
+                }
+                // Plain assignment may add a new key to a map
+                element_mut(container, last, operator == AssignmentOperator::Assign)?
+            }
+        };
+
+        let new_value = match compound_operator(&operator) {
+            Some(op) => self.apply_binary_operator(op, slot.clone(), value)?,
+            None => value,
+        };
+        let new_value = match field_type {
+            Some((field, definition, index)) => {
+                self.enforce_type(new_value, Some(&definition.fields[index].1), || {
+                    format!("field '{}' of {}", field, definition.name)
+                })?
+            }
+            None => new_value,
+        };
+        *slot = new_value.clone();
+
+        // Variables keep their declared types too, checked as a whole
+        let root = match self.environment.annotation(&name) {
+            Some(ty) => self.enforce_type(root, Some(&ty), || format!("variable '{}'", name))?,
+            None => root,
+        };
+        let new_value = if path.is_empty() {
+            root.clone()
+        } else {
+            new_value
+        };
+        self.environment.set(&name, root)?;
+        Ok(new_value)
+    }
+
+    /// Splits the object of an index or field target into the variable at its
+    /// root and the keys leading from that variable to the object.
+    fn assignment_path(&mut self, expr: Expr) -> Result<(String, Vec<Value>)> {
+        match expr {
+            Expr::Identifier(name) => Ok((name, Vec::new())),
+            Expr::Index { object, index } => {
+                let (name, mut path) = self.assignment_path(*object)?;
+                path.push(self.evaluate_expression(*index)?);
+                Ok((name, path))
+            }
+            Expr::FieldAccess { object, field } => {
+                let (name, mut path) = self.assignment_path(*object)?;
+                path.push(Value::String(field));
+                Ok((name, path))
+            }
+            _ => Err(anyhow!("Invalid assignment target")),
+        }
+    }
+}
+
+/// Reads `object[index]`. Negative indices count from the end, and strings
+/// are indexed by grapheme cluster, so `"e\u{301}"[0]` is the whole "é".
+/// Indexing an array or string with a range slices it.
+pub(super) fn index_value(object: Value, index: Value) -> Result<Value> {
+    match (object, index) {
+        (Value::Array(items), Value::Range(range)) => Ok(Value::Array(slice(&items, range)?)),
+        (Value::String(s), Value::Range(range)) => {
+            let graphemes: Vec<&str> = s.graphemes(true).collect();
+            Ok(Value::String(slice(&graphemes, range)?.concat()))
+        }
+        (Value::Array(items) | Value::Tuple(items), Value::Integer(i)) => {
+            normalize_index(i, items.len())
+                .map(|i| items[i].clone())
+                .ok_or_else(|| index_error(i, items.len()))
+        }
+        (Value::String(s), Value::Integer(i)) => {
+            let graphemes: Vec<&str> = s.graphemes(true).collect();
+            normalize_index(i, graphemes.len())
+                .map(|i| Value::String(graphemes[i].to_string()))
+                .ok_or_else(|| index_error(i, graphemes.len()))
+        }
+        (Value::Range(range), Value::Integer(i)) => range
+            .get(i)
+            .map(Value::Integer)
+            .ok_or_else(|| index_error(i, range.len())),
+        // Nothing is long enough to have an index beyond i64
+        (Value::Array(items) | Value::Tuple(items), Value::BigInt(i)) => {
+            Err(index_error(i, items.len()))
+        }
+        (Value::String(s), Value::BigInt(i)) => Err(index_error(i, s.graphemes(true).count())),
+        (Value::Range(range), Value::BigInt(i)) => Err(index_error(i, range.len())),
+        (Value::Map(map), Value::String(key)) => {
+            map.get(&key).cloned().ok_or_else(|| key_error(&key))
+        }
+        (object, index) => Err(anyhow!(
+            "Cannot index {} with {}",
+            object.type_name(),
+            index.type_name()
+        )),
+    }
+}
+
+/// Reads `object.field`: map entries, or the parts of an error value.
+pub(super) fn field_value(object: Value, field: String) -> Result<Value> {
+    match object {
+        Value::Map(map) => map.get(&field).cloned().ok_or_else(|| key_error(&field)),
+        Value::Error {
+            kind,
+            message,
+            fields,
+        } => match field.as_str() {
+            "kind" => Ok(Value::String(kind)),
+            "message" => Ok(Value::String(message)),
+            _ => fields
+                .get(&field)
+                .cloned()
+                .ok_or_else(|| anyhow!("{} error has no field '{}'", kind, field)),
+        },
+        Value::EnumType(definition) => unit_variant(&definition, &field),
+        Value::Struct { definition, fields } => match definition.field_index(&field) {
+            Some(index) => Ok(fields[index].clone()),
+            None => Err(no_field_error(&definition.name, &field)),
+        },
+        other => Err(anyhow!(
+            "Field access not supported for type {}",
+            other.type_name()
+        )),
+    }
+}
+
+fn compound_operator(operator: &AssignmentOperator) -> Option<BinaryOperator> {
+    match operator {
+        AssignmentOperator::Assign => None,
+        AssignmentOperator::AddAssign => Some(BinaryOperator::Add),
+        AssignmentOperator::SubtractAssign => Some(BinaryOperator::Subtract),
+        AssignmentOperator::MultiplyAssign => Some(BinaryOperator::Multiply),
+        AssignmentOperator::DivideAssign => Some(BinaryOperator::Divide),
+        AssignmentOperator::ModuloAssign => Some(BinaryOperator::Modulo),
+    }
+}
+
+/// Borrows the element of `container` at `key` for writing. With `insert`,
+/// a missing map key is created as `nil` first.
+fn element_mut<'a>(container: &'a mut Value, key: &Value, insert: bool) -> Result<&'a mut Value> {
+    match (container, key) {
+        (Value::Array(items), Value::Integer(i)) => {
+            let len = items.len();
+            normalize_index(*i, len)
+                .map(move |i| &mut items[i])
+                .ok_or_else(|| index_error(*i, len))
+        }
+        (Value::Array(items), Value::BigInt(i)) => Err(index_error(i.clone(), items.len())),
+        (Value::Map(map), Value::String(key)) => {
+            if insert {
+                Ok(map.entry(key.clone()).or_insert(Value::Nil))
+            } else {
+                map.get_mut(key).ok_or_else(|| key_error(key))
+            }
+        }
+        // Structs never gain fields
+        (Value::Struct { definition, fields }, Value::String(field)) => {
+            match definition.field_index(field) {
+                Some(index) => Ok(&mut fields[index]),
+                None => Err(no_field_error(&definition.name, field)),
+            }
+        }
+        (container, key) => Err(anyhow!(
+            "Cannot assign to {} element with {} key",
+            container.type_name(),
+            key.type_name()
+        )),
+    }
+}
+
+/// The elements at each index of `range`, which must all be in bounds.
+fn slice<T: Clone>(items: &[T], range: Range) -> Result<Vec<T>> {
+    range
+        .iter()
+        .map(|i| {
+            normalize_index(i, items.len())
+                .map(|i| items[i].clone())
+                .ok_or_else(|| index_error(i, items.len()))
+        })
+        .collect()
+}
+
+fn normalize_index(index: i64, len: usize) -> Option<usize> {
+    let index = if index < 0 {
+        len as i128 + index as i128
+    } else {
+        index as i128
+    };
+    if (0..len as i128).contains(&index) {
+        Some(index as usize)
+    } else {
+        None
+    }
+}
+
+fn index_error(index: impl Into<BigInt>, len: impl fmt::Display) -> anyhow::Error {
+    let index = index.into();
+    ScriptError::new(
+        "IndexError",
+        format!("Index {} out of bounds for length {}", index, len),
+    )
+    .with_field("index", index.into())
+    .into()
+}
+
+fn no_field_error(name: &str, field: &str) -> anyhow::Error {
+    anyhow!("{} has no field '{}'", name, field)
+}
+
+fn key_error(key: &str) -> anyhow::Error {
+    ScriptError::new("KeyError", format!("Key '{}' not found", key))
+        .with_field("key", Value::String(key.to_string()))
+        .into()
+}
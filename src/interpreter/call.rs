@@ -0,0 +1,156 @@
+use super::{Interpreter, Value};
+use crate::parser::ast::{Argument, Parameter};
+use anyhow::{anyhow, Result};
+
+/// Evaluated call arguments: positional values with spreads expanded, then
+/// `name: value` pairs in the order they were written.
+#[derive(Debug, Default)]
+pub(super) struct CallArgs {
+    pub positional: Vec<Value>,
+    pub named: Vec<(String, Value)>,
+}
+
+impl From<Vec<Value>> for CallArgs {
+    fn from(positional: Vec<Value>) -> Self {
+        Self {
+            positional,
+            named: Vec::new(),
+        }
+    }
+}
+
+impl CallArgs {
+    /// The positional values, for callees such as built-ins and external
+    /// commands that have no parameter names.
+    pub fn into_positional(self, callee: &str) -> Result<Vec<Value>> {
+        match self.named.first() {
+            Some((name, _)) => Err(anyhow!(
+                "{} does not take named arguments, got '{}'",
+                callee,
+                name
+            )),
+            None => Ok(self.positional),
+        }
+    }
+}
+
+impl Interpreter {
+    pub(super) fn evaluate_call_arguments(&mut self, args: Vec<Argument>) -> Result<CallArgs> {
+        let mut call_args = CallArgs::default();
+        for arg in args {
+            match arg {
+                Argument::Positional(expr) => {
+                    call_args.positional.push(self.evaluate_expression(expr)?)
+                }
+                Argument::Spread(expr) => match self.evaluate_expression(expr)? {
+                    Value::Array(items) | Value::Tuple(items) => call_args.positional.extend(items),
+                    other => {
+                        return Err(anyhow!(
+                            "Cannot spread {} into arguments, expected an array",
+                            other.type_name()
+                        ))
+                    }
+                },
+                Argument::Named(name, expr) => {
+                    let value = self.evaluate_expression(expr)?;
+                    call_args.named.push((name, value));
+                }
+            }
+        }
+        Ok(call_args)
+    }
+
+    /// Binds `args` to `parameters` in the current scope, which must be the
+    /// new scope of the call.
+    ///
+    /// Defaults are evaluated in that scope, so they can refer to the
+    /// parameters before them, and a variadic parameter receives an array of
+    /// the remaining positional arguments, each checked against its type.
+    pub(super) fn bind_arguments(
+        &mut self,
+        function: &str,
+        parameters: &[Parameter],
+        args: CallArgs,
+    ) -> Result<()> {
+        let fixed = parameters.iter().take_while(|p| !p.variadic).count();
+        let variadic = parameters.get(fixed);
+        let required = parameters.iter().filter(|p| p.default.is_none()).count()
+            - usize::from(variadic.is_some());
+
+        let count = args.positional.len();
+        if (count > fixed && variadic.is_none()) || (count < required && args.named.is_empty()) {
+            let (expected, singular) = match (variadic, required == fixed) {
+                (Some(_), _) => (format!("at least {}", required), required == 1),
+                (None, true) => (fixed.to_string(), fixed == 1),
+                (None, false) => (format!("{} to {}", required, fixed), false),
+            };
+            return Err(anyhow!(
+                "Function {} expects {} argument{}, got {}",
+                function,
+                expected,
+                if singular { "" } else { "s" },
+                count
+            ));
+        }
+
+        let mut positional = args.positional.into_iter();
+        let mut values: Vec<Option<Value>> = positional.by_ref().take(fixed).map(Some).collect();
+        values.resize(fixed, None);
+
+        for (name, value) in args.named {
+            let Some(index) = parameters[..fixed].iter().position(|p| p.name == name) else {
+                return Err(match variadic {
+                    Some(p) if p.name == name => anyhow!(
+                        "Variadic parameter '{}' of {} cannot be passed by name",
+                        name,
+                        function
+                    ),
+                    _ => anyhow!("Function {} has no parameter '{}'", function, name),
+                });
+            };
+            if values[index].is_some() {
+                return Err(anyhow!(
+                    "Function {} got more than one value for '{}'",
+                    function,
+                    name
+                ));
+            }
+            values[index] = Some(value);
+        }
+
+        for (parameter, value) in parameters.iter().zip(values) {
+            let value = match (value, &parameter.default) {
+                (Some(value), _) => value,
+                (None, Some(default)) => self.evaluate_expression(default.clone())?,
+                (None, None) => {
+                    return Err(anyhow!(
+                        "Function {} is missing argument '{}'",
+                        function,
+                        parameter.name
+                    ))
+                }
+            };
+            let value = self.enforce_type(value, parameter.type_annotation.as_ref(), || {
+                format!("argument '{}' to {}", parameter.name, function)
+            })?;
+            self.environment.define_annotated(
+                parameter.name.clone(),
+                value,
+                parameter.type_annotation.as_ref(),
+            );
+        }
+
+        if let Some(parameter) = variadic {
+            let rest = positional
+                .map(|value| {
+                    self.enforce_type(value, parameter.type_annotation.as_ref(), || {
+                        format!("argument '{}' to {}", parameter.name, function)
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            self.environment
+                .define(parameter.name.clone(), Value::Array(rest));
+        }
+        Ok(())
+    }
+}
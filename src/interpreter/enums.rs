@@ -0,0 +1,130 @@
+use super::{ControlFlow, Interpreter, Value};
+use crate::parser::ast::Type;
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+
+/// An `enum` declaration: each variant with the types of its payload.
+#[derive(Debug)]
+pub struct EnumDef {
+    pub name: String,
+    pub variants: Vec<(String, Vec<Type>)>,
+}
+
+impl EnumDef {
+    fn variant(&self, name: &str) -> Result<&[Type]> {
+        self.variants
+            .iter()
+            .find(|(variant, _)| variant == name)
+            .map(|(_, payload)| payload.as_slice())
+            .ok_or_else(|| anyhow!("{} has no variant '{}'", self.name, name))
+    }
+}
+
+impl Value {
+    /// Builds a variant of the built-in `Result` and `Option` types.
+    pub fn builtin_variant(name: &str, values: Vec<Value>) -> Value {
+        let enum_name = match name {
+            "Ok" | "Err" => "Result",
+            _ => "Option",
+        };
+        Value::Variant {
+            enum_name: enum_name.to_string(),
+            name: name.to_string(),
+            values,
+        }
+    }
+
+    /// Whether this is a variant of the built-in `Result` or `Option`.
+    pub fn is_result_or_option(&self) -> bool {
+        matches!(self, Value::Variant { enum_name, .. } if enum_name == "Result" || enum_name == "Option")
+    }
+}
+
+impl Interpreter {
+    pub(super) fn define_enum(
+        &mut self,
+        name: String,
+        variants: Vec<(String, Vec<Type>)>,
+    ) -> Value {
+        let definition = Value::EnumType(Arc::new(EnumDef {
+            name: name.clone(),
+            variants,
+        }));
+        self.environment.define(name, definition.clone());
+        definition
+    }
+
+    /// Builds `Enum.Variant(args)`, checking the payload against the
+    /// variant's declared types.
+    pub(super) fn construct_variant(
+        &mut self,
+        definition: &EnumDef,
+        variant: &str,
+        args: Vec<Value>,
+    ) -> Result<Value> {
+        let payload = definition.variant(variant)?;
+        if payload.len() != args.len() {
+            return Err(anyhow!(
+                "{}.{} takes {} value{}, got {}",
+                definition.name,
+                variant,
+                payload.len(),
+                if payload.len() == 1 { "" } else { "s" },
+                args.len()
+            ));
+        }
+        let values = args
+            .into_iter()
+            .zip(payload)
+            .enumerate()
+            .map(|(position, (value, ty))| {
+                self.enforce_type(value, Some(ty), || {
+                    format!("value {} of {}.{}", position + 1, definition.name, variant)
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Value::Variant {
+            enum_name: definition.name.clone(),
+            name: variant.to_string(),
+            values,
+        })
+    }
+}
+
+/// Implements `value?`: `Ok(x)` and `Some(x)` unwrap to `x`, while `Err`
+/// and `None` are returned from the enclosing function as they are.
+pub(super) fn propagate(value: Value) -> Result<Value> {
+    match value {
+        Value::Variant {
+            ref name,
+            values: mut payload,
+            ..
+        } if (name == "Ok" || name == "Some") && payload.len() == 1 => Ok(payload.remove(0)),
+        Value::Variant { ref name, .. } if name == "Err" || name == "None" => {
+            Err(ControlFlow::Propagate(value).into())
+        }
+        other => Err(anyhow!(
+            "'?' expects a Result or Option, got {}",
+            other.type_name()
+        )),
+    }
+}
+
+/// Reads `Enum.Variant` for a variant without a payload.
+pub(super) fn unit_variant(definition: &EnumDef, variant: &str) -> Result<Value> {
+    let payload = definition.variant(variant)?;
+    if !payload.is_empty() {
+        return Err(anyhow!(
+            "{}.{} takes a value; construct it with {}.{}(...)",
+            definition.name,
+            variant,
+            definition.name,
+            variant
+        ));
+    }
+    Ok(Value::Variant {
+        enum_name: definition.name.clone(),
+        name: variant.to_string(),
+        values: Vec::new(),
+    })
+}
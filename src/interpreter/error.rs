@@ -0,0 +1,100 @@
+use super::Value;
+use std::collections::HashMap;
+use std::io;
+
+/// A runtime failure with a kind that `catch` clauses can match on.
+///
+/// Kinds raised by the interpreter include `FileNotFound`, `PermissionDenied`,
+/// `IoError`, `CommandNotFound`, `CommandFailed`, `DivisionByZero`,
+/// `IndexError`, `KeyError` and `TypeError`. Any other failure is seen by
+/// scripts as a `RuntimeError`.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{message}")]
+pub struct ScriptError {
+    pub kind: String,
+    pub message: String,
+    pub fields: HashMap<String, Value>,
+}
+
+impl ScriptError {
+    pub fn new(kind: &str, message: impl Into<String>) -> Self {
+        Self {
+            kind: kind.to_string(),
+            message: message.into(),
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Builds an error for a failed file operation on `path`.
+    pub fn io(err: &io::Error, message: impl Into<String>, path: &str) -> Self {
+        let kind = match err.kind() {
+            io::ErrorKind::NotFound => "FileNotFound",
+            io::ErrorKind::PermissionDenied => "PermissionDenied",
+            io::ErrorKind::AlreadyExists => "FileExists",
+            _ => "IoError",
+        };
+        Self::new(kind, message).with_field("path", Value::String(path.to_string()))
+    }
+
+    /// Builds the error for a command that ran but exited unsuccessfully.
+    pub fn command_failed(command: &str, exit_code: Option<i32>) -> Self {
+        let exit_code = exit_code.unwrap_or(-1);
+        Self::new(
+            "CommandFailed",
+            format!("Command '{}' failed with exit code {}", command, exit_code),
+        )
+        .with_field("command", Value::String(command.to_string()))
+        .with_field("exit_code", Value::Integer(exit_code as i64))
+    }
+
+    /// Builds the error for a command that could not be started.
+    pub fn command_spawn(command: &str, err: &io::Error) -> Self {
+        let error = if err.kind() == io::ErrorKind::NotFound {
+            Self::new("CommandNotFound", format!("Command not found: {}", command))
+        } else {
+            Self::new(
+                "CommandFailed",
+                format!("Failed to execute command '{}': {}", command, err),
+            )
+        };
+        error.with_field("command", Value::String(command.to_string()))
+    }
+
+    pub fn with_field(mut self, name: &str, value: Value) -> Self {
+        self.fields.insert(name.to_string(), value);
+        self
+    }
+
+    pub fn into_value(self) -> Value {
+        Value::Error {
+            kind: self.kind,
+            message: self.message,
+            fields: self.fields,
+        }
+    }
+
+    /// Turns a value passed to `throw` into an error; non-error values become
+    /// the message of a plain `Error`.
+    pub fn from_value(value: Value) -> Self {
+        match value {
+            Value::Error {
+                kind,
+                message,
+                fields,
+            } => Self {
+                kind,
+                message,
+                fields,
+            },
+            other => Self::new("Error", other.to_string()),
+        }
+    }
+}
+
+/// Converts a runtime failure into the error value bound by `catch ... as e`.
+pub fn error_to_value(err: anyhow::Error) -> Value {
+    match err.downcast::<ScriptError>() {
+        Ok(script_error) => script_error.into_value(),
+        Err(other) => ScriptError::new("RuntimeError", other.to_string()).into_value(),
+    }
+}
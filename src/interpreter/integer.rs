@@ -0,0 +1,160 @@
+use super::{ScriptError, Value};
+use crate::parser::ast::BinaryOperator;
+use anyhow::{anyhow, Result};
+use num_bigint::BigInt;
+use num_traits::{Signed, ToPrimitive, Zero};
+
+/// Integers wider than this raise an `OverflowError` instead of exhausting
+/// memory, as `10 ** 10 ** 9` would.
+const MAX_BITS: u64 = 1 << 20;
+
+/// Wraps an arbitrary-precision result, going back to `Value::Integer`
+/// whenever it fits, so a `Value::BigInt` is always outside the `i64` range.
+impl From<BigInt> for Value {
+    fn from(value: BigInt) -> Self {
+        match value.to_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::BigInt(value),
+        }
+    }
+}
+
+/// Applies `operator` to two `i64`s, promoting to a big integer when the
+/// result does not fit rather than wrapping or panicking.
+pub fn integer_operation(operator: BinaryOperator, l: i64, r: i64) -> Result<Value> {
+    let result = match operator {
+        BinaryOperator::Add => l.checked_add(r),
+        BinaryOperator::Subtract => l.checked_sub(r),
+        BinaryOperator::Multiply => l.checked_mul(r),
+        BinaryOperator::Divide | BinaryOperator::Modulo if r == 0 => return Err(division_by_zero()),
+        // Only `i64::MIN / -1` and `i64::MIN % -1` overflow
+        BinaryOperator::Divide => l.checked_div(r),
+        BinaryOperator::Modulo => l.checked_rem(r),
+        // A negative exponent gives a fraction, so the result is a float
+        BinaryOperator::Power if r < 0 => return Ok(Value::Float((l as f64).powf(r as f64))),
+        BinaryOperator::Power => u32::try_from(r).ok().and_then(|r| l.checked_pow(r)),
+        BinaryOperator::BitwiseAnd => Some(l & r),
+        BinaryOperator::BitwiseOr => Some(l | r),
+        BinaryOperator::BitwiseXor => Some(l ^ r),
+        BinaryOperator::LeftShift => {
+            let shift = shift_amount(&r.into())?;
+            // Shifting back must give `l` again, or bits were lost
+            (shift < u64::from(i64::BITS))
+                .then(|| l << shift)
+                .filter(|shifted| shifted >> shift == l)
+        }
+        // Past 63 places only the sign is left, giving 0 or -1
+        BinaryOperator::RightShift => Some(l >> shift_amount(&r.into())?.min(63)),
+        _ => return compare(operator, &l, &r),
+    };
+    match result {
+        Some(result) => Ok(Value::Integer(result)),
+        None => big_operation(operator, l.into(), r.into()),
+    }
+}
+
+/// Applies `operator` to integers of any size.
+pub fn big_operation(operator: BinaryOperator, l: BigInt, r: BigInt) -> Result<Value> {
+    let result = match operator {
+        BinaryOperator::Add => l + r,
+        BinaryOperator::Subtract => l - r,
+        BinaryOperator::Multiply => l * r,
+        BinaryOperator::Divide | BinaryOperator::Modulo if r.is_zero() => {
+            return Err(division_by_zero())
+        }
+        BinaryOperator::Divide => l / r,
+        BinaryOperator::Modulo => l % r,
+        BinaryOperator::Power if r.is_negative() => {
+            return Ok(Value::Float(to_f64(&l).powf(to_f64(&r))))
+        }
+        BinaryOperator::Power => power(l, r)?,
+        BinaryOperator::BitwiseAnd => l & r,
+        BinaryOperator::BitwiseOr => l | r,
+        BinaryOperator::BitwiseXor => l ^ r,
+        BinaryOperator::LeftShift => {
+            let shift = shift_amount(&r)?;
+            if l.is_zero() {
+                l
+            } else if shift >= MAX_BITS {
+                return Err(overflow());
+            } else {
+                l << shift
+            }
+        }
+        BinaryOperator::RightShift => {
+            let shift = shift_amount(&r)?.min(l.bits());
+            l >> shift
+        }
+        _ => return compare(operator, &l, &r),
+    };
+    if result.bits() > MAX_BITS {
+        return Err(overflow());
+    }
+    Ok(result.into())
+}
+
+/// The nearest float, or an infinity for integers beyond `f64::MAX`.
+pub fn to_f64(value: &BigInt) -> f64 {
+    value.to_f64().unwrap_or(if value.is_negative() {
+        f64::NEG_INFINITY
+    } else {
+        f64::INFINITY
+    })
+}
+
+fn compare<T: PartialOrd>(operator: BinaryOperator, l: &T, r: &T) -> Result<Value> {
+    match operator {
+        BinaryOperator::Equal => Ok(Value::Boolean(l == r)),
+        BinaryOperator::NotEqual => Ok(Value::Boolean(l != r)),
+        BinaryOperator::Less => Ok(Value::Boolean(l < r)),
+        BinaryOperator::LessEqual => Ok(Value::Boolean(l <= r)),
+        BinaryOperator::Greater => Ok(Value::Boolean(l > r)),
+        BinaryOperator::GreaterEqual => Ok(Value::Boolean(l >= r)),
+        _ => Err(anyhow!("Unsupported operator for integers: {:?}", operator)),
+    }
+}
+
+fn power(base: BigInt, exponent: BigInt) -> Result<BigInt> {
+    // 0, 1 and -1 stay small whatever the exponent
+    if base.abs() <= BigInt::from(1) {
+        let odd = exponent.bit(0);
+        return Ok(if base.is_zero() && exponent.is_zero() {
+            BigInt::from(1)
+        } else if base.is_negative() && !odd {
+            -base
+        } else {
+            base
+        });
+    }
+    // The result has at least (bits - 1) * exponent bits, so refuse before
+    // computing it when that is already too many
+    match exponent.to_u32() {
+        Some(exponent) if (base.bits() - 1).saturating_mul(exponent.into()) < MAX_BITS => {
+            Ok(base.pow(exponent))
+        }
+        _ => Err(overflow()),
+    }
+}
+
+/// The number of places to shift by. Amounts too large for a `u64` only
+/// matter for their sign, so they saturate.
+fn shift_amount(amount: &BigInt) -> Result<u64> {
+    if amount.is_negative() {
+        return Err(
+            ScriptError::new("ValueError", format!("Negative shift amount {}", amount)).into(),
+        );
+    }
+    Ok(amount.to_u64().unwrap_or(u64::MAX))
+}
+
+fn division_by_zero() -> anyhow::Error {
+    ScriptError::new("DivisionByZero", "Division by zero").into()
+}
+
+fn overflow() -> anyhow::Error {
+    ScriptError::new(
+        "OverflowError",
+        format!("Integer result is larger than {} bits", MAX_BITS),
+    )
+    .into()
+}
@@ -0,0 +1,290 @@
+use super::{ControlFlow, Environment, Interpreter, ScriptError, Value};
+use crate::parser::ast::{Argument, Expr, InterpolationPart, Stmt};
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Lines};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+
+type Source = Box<dyn Iterator<Item = Result<Value>> + Send>;
+
+/// A lazy, single-pass sequence of values: the result of calling a
+/// generator function, or of streaming built-ins such as `read_lines`.
+/// Copies share one position, so a value taken by one is gone for all.
+#[derive(Clone)]
+pub struct LazyIterator(Arc<Mutex<Source>>);
+
+impl LazyIterator {
+    pub fn new(source: impl Iterator<Item = Result<Value>> + Send + 'static) -> Self {
+        Self(Arc::new(Mutex::new(Box::new(source))))
+    }
+
+    /// Streams the lines of a file without reading it all at once.
+    pub fn file_lines(path: &str) -> Result<Self> {
+        let file = File::open(path).map_err(|e| {
+            ScriptError::io(&e, format!("Failed to read file {}: {}", path, e), path)
+        })?;
+        let path = path.to_string();
+        Ok(Self::new(BufReader::new(file).lines().map(move |line| {
+            line.map(Value::String)
+                .map_err(|e| anyhow!("Cannot read '{}': {}", path, e))
+        })))
+    }
+
+    /// Streams the output lines of a shell command while it runs.
+    pub fn command_lines(command: &str) -> Result<Self> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| anyhow!("Failed to execute command: {}", e))?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        Ok(Self::new(CommandLines {
+            child,
+            lines: BufReader::new(stdout).lines(),
+        }))
+    }
+
+    pub fn next_value(&self) -> Option<Result<Value>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner).next()
+    }
+}
+
+impl Iterator for LazyIterator {
+    type Item = Result<Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_value()
+    }
+}
+
+impl fmt::Debug for LazyIterator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<iterator>")
+    }
+}
+
+impl PartialEq for LazyIterator {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+struct CommandLines {
+    child: Child,
+    lines: Lines<BufReader<ChildStdout>>,
+}
+
+impl Iterator for CommandLines {
+    type Item = Result<Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = self.lines.next()?;
+        Some(line.map(Value::String).map_err(|e| anyhow!("{}", e)))
+    }
+}
+
+// A loop that stops early must not leave the command running
+impl Drop for CommandLines {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The generator's end of the hand-off: each `yield` sends a value and
+/// then waits until the consumer asks for the next one.
+pub(super) struct Yielder {
+    values: SyncSender<Result<Value>>,
+    resume: Receiver<()>,
+}
+
+/// Runs a generator function's body on its own thread, one value at a
+/// time. Nothing runs until the first value is requested.
+struct Generator {
+    start: Option<Box<dyn FnOnce(Yielder) + Send>>,
+    resume: Option<SyncSender<()>>,
+    values: Option<Receiver<Result<Value>>>,
+}
+
+impl Iterator for Generator {
+    type Item = Result<Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(start) = self.start.take() {
+            // Rendezvous channels keep the two threads in lockstep
+            let (resume, resumed) = sync_channel(0);
+            let (yielded, values) = sync_channel(0);
+            thread::spawn(move || {
+                start(Yielder {
+                    values: yielded,
+                    resume: resumed,
+                })
+            });
+            self.resume = Some(resume);
+            self.values = Some(values);
+        }
+        let next = self
+            .resume
+            .as_ref()?
+            .send(())
+            .ok()
+            .and_then(|()| self.values.as_ref()?.recv().ok());
+        if next.is_none() {
+            self.resume = None;
+            self.values = None;
+        }
+        next
+    }
+}
+
+impl Interpreter {
+    /// Turns a call of a generator function into an iterator over the values
+    /// its body yields. Arguments are already bound in `environment`.
+    pub(super) fn generator(&self, environment: Environment, body: Vec<Stmt>) -> Value {
+        let runtime_types = self.runtime_types;
+        let script_path: Option<PathBuf> = self.script_path.clone();
+        let modules = self.modules.clone();
+        let start = move |yielder: Yielder| {
+            // Wait for the first request before running anything
+            if yielder.resume.recv().is_err() {
+                return;
+            }
+            let mut interpreter = Interpreter {
+                environment,
+                runtime_types,
+                script_path,
+                modules,
+                yielder: Some(yielder),
+                exit_code: None,
+            };
+            let result = interpreter.execute_block(body);
+            let Some(yielder) = interpreter.yielder.take() else {
+                return;
+            };
+            if matches!(result, Err(ref e) if matches!(e.downcast_ref(), Some(ControlFlow::Closed)))
+            {
+                return;
+            }
+            if let Err(e) = Interpreter::function_result(result) {
+                let _ = yielder.values.send(Err(e));
+            }
+        };
+        Value::Iterator(LazyIterator::new(Generator {
+            start: Some(Box::new(start)),
+            resume: None,
+            values: None,
+        }))
+    }
+
+    /// Hands `value` to the consumer and waits to be resumed.
+    pub(super) fn yield_value(&mut self, value: Value) -> Result<()> {
+        let yielder = self
+            .yielder
+            .as_ref()
+            .ok_or_else(|| anyhow!("'yield' outside of a generator function"))?;
+        if yielder.values.send(Ok(value)).is_err() || yielder.resume.recv().is_err() {
+            return Err(ControlFlow::Closed.into());
+        }
+        Ok(())
+    }
+}
+
+/// Whether `body` yields, which makes its function a generator. Nested
+/// functions, lambdas and spawned blocks run on their own, so are not searched.
+pub(super) fn contains_yield(body: &[Stmt]) -> bool {
+    body.iter().any(|stmt| match stmt {
+        Stmt::Yield { .. } => true,
+        Stmt::Expression(value)
+        | Stmt::Let { value, .. }
+        | Stmt::Const { value, .. }
+        | Stmt::Assignment { value, .. }
+        | Stmt::Destructure { value, .. }
+        | Stmt::Return {
+            value: Some(value), ..
+        } => expr_contains_yield(value),
+        Stmt::If {
+            condition,
+            then_block,
+            else_block,
+        } => {
+            expr_contains_yield(condition)
+                || contains_yield(then_block)
+                || else_block.as_deref().is_some_and(contains_yield)
+        }
+        Stmt::For { iterable, body, .. } => expr_contains_yield(iterable) || contains_yield(body),
+        Stmt::While { condition, body } => expr_contains_yield(condition) || contains_yield(body),
+        Stmt::Try {
+            body,
+            catch_clauses,
+        } => contains_yield(body) || catch_clauses.iter().any(|c| contains_yield(&c.body)),
+        Stmt::Match { expr, arms } => {
+            expr_contains_yield(expr) || arms.iter().any(|arm| contains_yield(&arm.body))
+        }
+        _ => false,
+    })
+}
+
+/// Whether evaluating `expr` can reach a `yield`, through `if` and block
+/// expressions or any operand that contains one.
+fn expr_contains_yield(expr: &Expr) -> bool {
+    let args = |args: &[Argument]| {
+        args.iter().any(|arg| match arg {
+            Argument::Positional(value) | Argument::Named(_, value) | Argument::Spread(value) => {
+                expr_contains_yield(value)
+            }
+        })
+    };
+    match expr {
+        Expr::If {
+            condition,
+            then_block,
+            else_block,
+        } => {
+            expr_contains_yield(condition)
+                || contains_yield(then_block)
+                || else_block.as_deref().is_some_and(contains_yield)
+        }
+        Expr::Block(body) => contains_yield(body),
+        Expr::Binary { left, right, .. } | Expr::Pipeline { left, right } => {
+            expr_contains_yield(left) || expr_contains_yield(right)
+        }
+        Expr::Unary { operand: value, .. }
+        | Expr::Await(value)
+        | Expr::Propagate(value)
+        | Expr::FieldAccess { object: value, .. }
+        | Expr::OptionalFieldAccess { object: value, .. } => expr_contains_yield(value),
+        Expr::Index { object, index } => expr_contains_yield(object) || expr_contains_yield(index),
+        Expr::Call {
+            callee, args: a, ..
+        } => expr_contains_yield(callee) || args(a),
+        Expr::MethodCall {
+            object, args: a, ..
+        } => expr_contains_yield(object) || args(a),
+        Expr::Array(items) | Expr::Tuple(items) => items.iter().any(expr_contains_yield),
+        Expr::Map(entries) => entries
+            .iter()
+            .any(|(key, value)| expr_contains_yield(key) || expr_contains_yield(value)),
+        Expr::StructLiteral { fields, .. } => {
+            fields.iter().any(|(_, value)| expr_contains_yield(value))
+        }
+        Expr::Interpolation(parts) => parts.iter().any(|part| match part {
+            InterpolationPart::Expression(value) => expr_contains_yield(value),
+            InterpolationPart::Text(_) => false,
+        }),
+        Expr::Range {
+            start, end, step, ..
+        } => {
+            expr_contains_yield(start)
+                || expr_contains_yield(end)
+                || step.as_deref().is_some_and(expr_contains_yield)
+        }
+        _ => false,
+    }
+}
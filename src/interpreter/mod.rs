@@ -1,38 +1,111 @@
-use crate::builtins::{call_builtin, is_builtin, register_builtins};
+mod access;
+mod call;
+mod enums;
+mod error;
+mod integer;
+mod iterator;
+mod module;
+mod pattern;
+mod range;
+mod structs;
+mod task;
+mod types;
+
+use call::CallArgs;
+use enums::propagate;
+pub use enums::EnumDef;
+pub use error::{error_to_value, ScriptError};
+pub use integer::to_f64;
+pub use iterator::LazyIterator;
+use iterator::{contains_yield, Yielder};
+pub use range::Range;
+use structs::struct_method;
+pub use structs::StructDef;
+pub use task::{await_task, Channel, Task};
+
+use crate::builtins::{call_builtin, call_method, is_builtin, register_builtins, repeat_string};
 use crate::parser::ast::*;
 use anyhow::{anyhow, Result};
-use std::collections::HashMap;
+use num_bigint::BigInt;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use unicode_segmentation::UnicodeSegmentation;
 
 use std::process::Command;
 
+/// The values a `for` loop walks over; lazy sources can fail part way.
+type ValueStream = Box<dyn Iterator<Item = Result<Value>>>;
+
 #[derive(Debug, Clone)]
 pub enum Value {
     Integer(i64),
+    // An integer outside the i64 range, produced when arithmetic overflows
+    BigInt(BigInt),
     Float(f64),
     String(String),
     Boolean(bool),
     Nil,
     Array(Vec<Value>),
+    Tuple(Vec<Value>),
     Map(HashMap<String, Value>),
+    Range(Range),
     Function {
         name: String,
         parameters: Vec<Parameter>,
+        return_type: Option<Type>,
         body: Vec<Stmt>,
         closure: Environment,
     },
+    Error {
+        kind: String,
+        message: String,
+        fields: HashMap<String, Value>,
+    },
+    // An instance of a struct, with field values in declaration order
+    Struct {
+        definition: Arc<StructDef>,
+        fields: Vec<Value>,
+    },
+    // The struct itself, bound to its name by `struct Name { ... }`
+    StructType(Arc<StructDef>),
+    // A value of an enum, including the built-in `Result` and `Option`
+    Variant {
+        enum_name: String,
+        name: String,
+        values: Vec<Value>,
+    },
+    // The enum itself, bound to its name by `enum Name { ... }`
+    EnumType(Arc<EnumDef>),
+    // A lazy sequence from a generator or a streaming built-in
+    Iterator(LazyIterator),
+    // A block started by `spawn`, and a queue for passing values between tasks
+    Task(Task),
+    Channel(Channel),
 }
 
 impl Value {
-    pub fn type_name(&self) -> &'static str {
+    pub fn type_name(&self) -> &str {
         match self {
-            Value::Integer(_) => "int",
+            Value::Integer(_) | Value::BigInt(_) => "int",
             Value::Float(_) => "float",
             Value::String(_) => "string",
             Value::Boolean(_) => "bool",
             Value::Nil => "nil",
             Value::Array(_) => "array",
+            Value::Tuple(_) => "tuple",
             Value::Map(_) => "map",
+            Value::Range(_) => "range",
             Value::Function { .. } => "function",
+            Value::Error { .. } => "error",
+            Value::Struct { definition, .. } => &definition.name,
+            Value::StructType(_) => "struct",
+            Value::Variant { enum_name, .. } => enum_name,
+            Value::EnumType(_) => "enum",
+            Value::Iterator(_) => "iterator",
+            Value::Task(_) => "task",
+            Value::Channel(_) => "channel",
         }
     }
 
@@ -44,52 +117,167 @@ impl Value {
             Value::Float(f) if *f == 0.0 => false,
             Value::String(s) if s.is_empty() => false,
             Value::Array(arr) if arr.is_empty() => false,
+            Value::Tuple(items) if items.is_empty() => false,
             Value::Map(map) if map.is_empty() => false,
+            Value::Range(range) if range.is_empty() => false,
             _ => true,
         }
     }
+}
 
-    pub fn to_string(&self) -> String {
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Value::Integer(i) => i.to_string(),
-            Value::Float(f) => f.to_string(),
-            Value::String(s) => s.clone(),
-            Value::Boolean(b) => b.to_string(),
-            Value::Nil => "nil".to_string(),
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::BigInt(i) => write!(f, "{}", i),
+            Value::Float(n) => write!(f, "{}", n),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Nil => write!(f, "nil"),
             Value::Array(arr) => {
                 let elements: Vec<String> = arr.iter().map(|v| v.to_string()).collect();
-                format!("[{}]", elements.join(", "))
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Value::Tuple(items) => {
+                let elements: Vec<String> = items.iter().map(|v| v.to_string()).collect();
+                write!(f, "({})", elements.join(", "))
             }
             Value::Map(map) => {
-                let pairs: Vec<String> = map
+                let pairs: Vec<String> = map.iter().map(|(k, v)| format!("{}: {}", k, v)).collect();
+                write!(f, "{{{}}}", pairs.join(", "))
+            }
+            Value::Range(range) => write!(f, "{}", range),
+            Value::Function { name, .. } => write!(f, "function {}", name),
+            Value::Error { kind, message, .. } => write!(f, "{}: {}", kind, message),
+            Value::Struct { definition, fields } => {
+                let pairs: Vec<String> = definition
+                    .fields
                     .iter()
-                    .map(|(k, v)| format!("{}: {}", k, v.to_string()))
+                    .zip(fields)
+                    .map(|((name, _), value)| format!("{}: {}", name, value))
                     .collect();
-                format!("{{{}}}", pairs.join(", "))
+                write!(f, "{} {{{}}}", definition.name, pairs.join(", "))
             }
-            Value::Function { name, .. } => format!("function {}", name),
+            Value::StructType(definition) => write!(f, "struct {}", definition.name),
+            Value::Variant { name, values, .. } if values.is_empty() => write!(f, "{}", name),
+            Value::Variant { name, values, .. } => {
+                let elements: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+                write!(f, "{}({})", name, elements.join(", "))
+            }
+            Value::EnumType(definition) => write!(f, "enum {}", definition.name),
+            Value::Iterator(_) => write!(f, "<iterator>"),
+            Value::Task(_) => write!(f, "<task>"),
+            Value::Channel(_) => write!(f, "<channel>"),
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub struct Environment {
+/// Non-local control flow raised by `break`, `continue`, `return` and `?`,
+/// and by `yield` once nothing is left to receive the value.
+///
+/// Signals travel through `execute_block` as errors. Loops catch `Break` and
+/// `Continue`, and function calls catch `Return` and `Propagate`, so they
+/// unwind correctly through nested blocks.
+#[derive(Debug, thiserror::Error)]
+pub enum ControlFlow {
+    #[error("'break' outside of a loop")]
+    Break,
+    #[error("'continue' outside of a loop")]
+    Continue,
+    #[error("'return' outside of a function")]
+    Return(Value),
+    // An `Err` or `None` that `?` returns from the enclosing function
+    #[error("Unhandled {0} from '?' outside of a function")]
+    Propagate(Value),
+    // Unwinds a generator whose consumer stopped asking for values
+    #[error("generator closed")]
+    Closed,
+}
+
+/// A lexical scope. Cloning an `Environment` yields another handle to the
+/// same scope, so blocks, closures and function calls all see each other's
+/// assignments.
+#[derive(Clone, Default)]
+pub struct Environment(Arc<Mutex<Scope>>);
+
+#[derive(Default)]
+struct Scope {
     variables: HashMap<String, Value>,
     constants: HashMap<String, bool>,
-    parent: Option<Box<Environment>>,
+    // Declared types, which assignments to the variable must keep
+    types: HashMap<String, Type>,
+    // Names declared with `pub`, visible to modules that import this one
+    exports: HashSet<String>,
+    parent: Option<Environment>,
+}
+
+// Scopes can reach themselves through the closures stored in them, so only
+// identity is compared and nothing inside is printed.
+impl fmt::Debug for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Environment").finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for Environment {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
 }
 
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Value::Integer(a), Value::Integer(b)) => a == b,
+            (Value::BigInt(a), Value::BigInt(b)) => a == b,
             (Value::Float(a), Value::Float(b)) => (a - b).abs() < f64::EPSILON,
             (Value::String(a), Value::String(b)) => a == b,
             (Value::Boolean(a), Value::Boolean(b)) => a == b,
             (Value::Nil, Value::Nil) => true,
             (Value::Array(a), Value::Array(b)) => a == b,
+            (Value::Tuple(a), Value::Tuple(b)) => a == b,
             (Value::Map(a), Value::Map(b)) => a == b,
+            (Value::Range(a), Value::Range(b)) => a == b,
             (Value::Function { name: a, .. }, Value::Function { name: b, .. }) => a == b,
+            (
+                Value::Error {
+                    kind: ka,
+                    message: ma,
+                    fields: fa,
+                },
+                Value::Error {
+                    kind: kb,
+                    message: mb,
+                    fields: fb,
+                },
+            ) => ka == kb && ma == mb && fa == fb,
+            (
+                Value::Struct {
+                    definition: da,
+                    fields: fa,
+                },
+                Value::Struct {
+                    definition: db,
+                    fields: fb,
+                },
+            ) => Arc::ptr_eq(da, db) && fa == fb,
+            (Value::StructType(a), Value::StructType(b)) => Arc::ptr_eq(a, b),
+            (
+                Value::Variant {
+                    enum_name: ea,
+                    name: na,
+                    values: va,
+                },
+                Value::Variant {
+                    enum_name: eb,
+                    name: nb,
+                    values: vb,
+                },
+            ) => ea == eb && na == nb && va == vb,
+            (Value::EnumType(a), Value::EnumType(b)) => Arc::ptr_eq(a, b),
+            (Value::Iterator(a), Value::Iterator(b)) => a == b,
+            (Value::Task(a), Value::Task(b)) => a == b,
+            (Value::Channel(a), Value::Channel(b)) => a == b,
             _ => false,
         }
     }
@@ -97,55 +285,94 @@ impl PartialEq for Value {
 
 impl Environment {
     pub fn new() -> Self {
-        Self {
-            variables: HashMap::new(),
-            constants: HashMap::new(),
-            parent: None,
-        }
+        Self::default()
     }
 
     pub fn new_with_parent(parent: Environment) -> Self {
-        Self {
+        Self(Arc::new(Mutex::new(Scope {
             variables: HashMap::new(),
             constants: HashMap::new(),
-            parent: Some(Box::new(parent)),
-        }
+            types: HashMap::new(),
+            exports: HashSet::new(),
+            parent: Some(parent),
+        })))
+    }
+
+    fn scope(&self) -> MutexGuard<'_, Scope> {
+        // A panic while holding the lock leaves the maps themselves intact
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
     }
 
-    pub fn define(&mut self, name: String, value: Value) {
+    pub fn define(&self, name: String, value: Value) {
         self.define_variable(name, value, false);
     }
 
-    pub fn define_const(&mut self, name: String, value: Value) {
+    pub fn define_const(&self, name: String, value: Value) {
         self.define_variable(name, value, true);
     }
 
-    fn define_variable(&mut self, name: String, value: Value, is_const: bool) {
-        self.variables.insert(name.clone(), value);
-        self.constants.insert(name, is_const);
+    fn define_variable(&self, name: String, value: Value, is_const: bool) {
+        let mut scope = self.scope();
+        scope.types.remove(&name);
+        scope.variables.insert(name.clone(), value);
+        scope.constants.insert(name, is_const);
+    }
+
+    /// Defines a variable whose later assignments must keep `annotation`.
+    pub fn define_annotated(&self, name: String, value: Value, annotation: Option<&Type>) {
+        self.define(name.clone(), value);
+        if let Some(ty) = annotation {
+            self.scope().types.insert(name, ty.clone());
+        }
+    }
+
+    /// The type `name` was declared with, from the scope that holds it.
+    pub fn annotation(&self, name: &str) -> Option<Type> {
+        let scope = self.scope();
+        if scope.variables.contains_key(name) {
+            scope.types.get(name).cloned()
+        } else {
+            scope.parent.as_ref()?.annotation(name)
+        }
+    }
+
+    pub fn export(&self, name: &str) {
+        self.scope().exports.insert(name.to_string());
+    }
+
+    /// The current values of the names this scope exports.
+    pub fn exports(&self) -> HashMap<String, Value> {
+        let scope = self.scope();
+        scope
+            .exports
+            .iter()
+            .filter_map(|name| Some((name.clone(), scope.variables.get(name)?.clone())))
+            .collect()
     }
 
     pub fn get(&self, name: &str) -> Option<Value> {
-        if let Some(value) = self.variables.get(name) {
+        let scope = self.scope();
+        if let Some(value) = scope.variables.get(name) {
             Some(value.clone())
-        } else if let Some(parent) = &self.parent {
+        } else if let Some(parent) = &scope.parent {
             parent.get(name)
         } else {
             None
         }
     }
 
-    pub fn set(&mut self, name: &str, value: Value) -> Result<()> {
-        if self.variables.contains_key(name) {
+    pub fn set(&self, name: &str, value: Value) -> Result<()> {
+        let mut scope = self.scope();
+        if scope.variables.contains_key(name) {
             // Check if this is a constant
-            if let Some(&is_const) = self.constants.get(name) {
+            if let Some(&is_const) = scope.constants.get(name) {
                 if is_const {
                     return Err(anyhow!("Cannot assign to const variable: {}", name));
                 }
             }
-            self.variables.insert(name.to_string(), value);
+            scope.variables.insert(name.to_string(), value);
             Ok(())
-        } else if let Some(parent) = &mut self.parent {
+        } else if let Some(parent) = &scope.parent {
             parent.set(name, value)
         } else {
             Err(anyhow!("Undefined variable: {}", name))
@@ -155,27 +382,79 @@ impl Environment {
 
 pub struct Interpreter {
     environment: Environment,
-    globals: Environment,
+    // Whether type annotations are checked as values are bound
+    runtime_types: bool,
+    // The file being run, which relative imports resolve against
+    script_path: Option<PathBuf>,
+    modules: module::Modules,
+    // Set while running the body of a generator, where `yield` hands values out
+    yielder: Option<Yielder>,
+    // Set by a top-level `return` of an integer, which the script exits with
+    exit_code: Option<i32>,
+}
+
+/// The exit status for a top-level `return`. Statuses are a single byte, so
+/// other integers are refused rather than truncated, which would let 256
+/// exit with 0.
+fn exit_status(code: &Value) -> Result<i32> {
+    match code {
+        Value::Integer(code) => u8::try_from(*code).ok().map(i32::from),
+        _ => None,
+    }
+    .ok_or_else(|| {
+        ScriptError::new(
+            "ValueError",
+            format!("Exit status {} is outside 0..=255", code),
+        )
+        .into()
+    })
 }
 
 impl Interpreter {
     pub fn new() -> Self {
-        let mut globals = Environment::new();
+        let globals = Environment::new();
 
         // Add built-in functions
-        register_builtins(&mut globals);
+        register_builtins(&globals);
 
         Self {
-            environment: globals.clone(),
-            globals,
+            environment: globals,
+            runtime_types: true,
+            script_path: None,
+            modules: module::Modules::default(),
+            yielder: None,
+            exit_code: None,
         }
     }
 
+    pub fn set_runtime_types(&mut self, enabled: bool) {
+        self.runtime_types = enabled;
+    }
+
+    /// The status the script asked to exit with through a top-level
+    /// `return`, as `exit(code)` would.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
     pub fn interpret(&mut self, program: Program) -> Result<Value> {
         let mut result = Value::Nil;
 
         for stmt in program.statements {
-            result = self.execute_statement(stmt)?;
+            match self.execute_statement(stmt) {
+                Ok(value) => result = value,
+                // A top-level `return` ends the script, like bash's `return`/`exit`
+                Err(e) => match e.downcast::<ControlFlow>() {
+                    Ok(ControlFlow::Return(value)) => {
+                        if let Value::Integer(_) | Value::BigInt(_) = value {
+                            self.exit_code = Some(exit_status(&value)?);
+                        }
+                        return Ok(value);
+                    }
+                    Ok(flow) => return Err(anyhow!("{}", flow)),
+                    Err(e) => return Err(e),
+                },
+            }
         }
 
         Ok(result)
@@ -185,27 +464,43 @@ impl Interpreter {
         match stmt {
             Stmt::Expression(expr) => self.evaluate_expression(expr),
 
-            Stmt::Let { name, value, .. } => {
+            Stmt::Let {
+                name,
+                type_annotation,
+                value,
+                ..
+            } => {
                 let val = self.evaluate_expression(value)?;
-                self.environment.define(name, val.clone());
+                let val = self.enforce_type(val, type_annotation.as_ref(), || {
+                    format!("variable '{}'", name)
+                })?;
+                self.environment
+                    .define_annotated(name, val.clone(), type_annotation.as_ref());
                 Ok(val)
             }
 
-            Stmt::Const { name, value, .. } => {
+            Stmt::Const {
+                name,
+                type_annotation,
+                value,
+                ..
+            } => {
                 let val = self.evaluate_expression(value)?;
+                let val = self.enforce_type(val, type_annotation.as_ref(), || {
+                    format!("constant '{}'", name)
+                })?;
                 self.environment.define_const(name, val.clone());
                 Ok(val)
             }
 
-            Stmt::Assignment { target, value, .. } => {
+            Stmt::Assignment {
+                target,
+                operator,
+                value,
+                ..
+            } => {
                 let val = self.evaluate_expression(value)?;
-                match target {
-                    AssignmentTarget::Identifier(name) => {
-                        self.environment.set(&name, val.clone())?;
-                    }
-                    _ => return Err(anyhow!("Complex assignment targets not yet supported")),
-                }
-                Ok(val)
+                self.assign(target, operator, val)
             }
 
             Stmt::If {
@@ -226,12 +521,14 @@ impl Interpreter {
             Stmt::Function {
                 name,
                 parameters,
+                return_type,
                 body,
                 ..
             } => {
                 let func = Value::Function {
                     name: name.clone(),
                     parameters,
+                    return_type,
                     body,
                     closure: self.environment.clone(),
                 };
@@ -239,50 +536,300 @@ impl Interpreter {
                 Ok(func)
             }
 
-            Stmt::Return(expr) => {
-                if let Some(e) = expr {
-                    self.evaluate_expression(e)
+            Stmt::Return { value: expr, .. } => {
+                let value = if let Some(e) = expr {
+                    self.evaluate_expression(e)?
                 } else {
-                    Ok(Value::Nil)
+                    Value::Nil
+                };
+                Err(ControlFlow::Return(value).into())
+            }
+
+            Stmt::Yield { value, .. } => {
+                let value = self.evaluate_expression(value)?;
+                self.yield_value(value)?;
+                Ok(Value::Nil)
+            }
+
+            Stmt::For {
+                variable,
+                iterable,
+                body,
+            } => {
+                for item in self.iteration_values(iterable)? {
+                    let item = item?;
+                    let result =
+                        self.execute_block_with(vec![(variable.clone(), item)], body.clone());
+                    if !Self::continue_loop(result)? {
+                        break;
+                    }
+                }
+                Ok(Value::Nil)
+            }
+
+            Stmt::While { condition, body } => {
+                while self.evaluate_expression(condition.clone())?.is_truthy() {
+                    let result = self.execute_block(body.clone());
+                    if !Self::continue_loop(result)? {
+                        break;
+                    }
+                }
+                Ok(Value::Nil)
+            }
+
+            Stmt::Try {
+                body,
+                catch_clauses,
+            } => match self.execute_block(body) {
+                Ok(value) => Ok(value),
+                Err(e) if e.is::<ControlFlow>() => Err(e),
+                Err(e) => {
+                    let error = error_to_value(e);
+                    let kind = match &error {
+                        Value::Error { kind, .. } => kind.clone(),
+                        _ => unreachable!("error_to_value always yields an error value"),
+                    };
+
+                    // `catch Error` and a bare `catch` handle every kind
+                    let clause = catch_clauses.into_iter().find(|clause| {
+                        clause
+                            .exception_type
+                            .as_ref()
+                            .is_none_or(|t| t == "Error" || *t == kind)
+                    });
+
+                    match clause {
+                        Some(clause) => {
+                            let bindings = clause
+                                .variable
+                                .map(|name| vec![(name, error)])
+                                .unwrap_or_default();
+                            self.execute_block_with(bindings, clause.body)
+                        }
+                        None => Err(ScriptError::from_value(error).into()),
+                    }
+                }
+            },
+
+            Stmt::Match { expr, arms } => {
+                let value = self.evaluate_expression(expr)?;
+                for arm in arms {
+                    let mut bindings = Vec::new();
+                    if !self.match_pattern(&arm.pattern, &value, &mut bindings)? {
+                        continue;
+                    }
+                    if let Some(guard) = arm.guard {
+                        let guard_stmt = vec![Stmt::Expression(guard)];
+                        if !self
+                            .execute_block_with(bindings.clone(), guard_stmt)?
+                            .is_truthy()
+                        {
+                            continue;
+                        }
+                    }
+                    return self.execute_block_with(bindings, arm.body);
+                }
+                Ok(Value::Nil)
+            }
+
+            Stmt::Import {
+                path, alias, names, ..
+            } => {
+                let exports = self.import_module(&path)?;
+                if names.is_empty() {
+                    // The whole module is a map, so `git.status()` calls its function
+                    let name = match alias {
+                        Some(alias) => alias,
+                        None => module::default_name(&path)?,
+                    };
+                    self.environment.define(name, Value::Map(exports));
+                } else {
+                    for name in names {
+                        let value = exports.get(&name).cloned().ok_or_else(|| {
+                            anyhow!("Module '{}' does not export '{}'", path, name)
+                        })?;
+                        self.environment.define(name, value);
+                    }
+                }
+                Ok(Value::Nil)
+            }
+
+            Stmt::Struct { name, fields, .. } => Ok(self.define_struct(name, fields)),
+
+            Stmt::Enum { name, variants, .. } => Ok(self.define_enum(name, variants)),
+
+            Stmt::Impl { name, methods, .. } => self.define_methods(&name, methods),
+
+            Stmt::Export(stmt) => {
+                let name = match stmt.as_ref() {
+                    Stmt::Function { name, .. }
+                    | Stmt::Let { name, .. }
+                    | Stmt::Const { name, .. }
+                    | Stmt::Struct { name, .. }
+                    | Stmt::Enum { name, .. } => name.clone(),
+                    _ => return Err(anyhow!("'pub' must precede a declaration")),
+                };
+                let value = self.execute_statement(*stmt)?;
+                self.environment.export(&name);
+                Ok(value)
+            }
+
+            Stmt::Destructure { pattern, value, .. } => {
+                let val = self.evaluate_expression(value)?;
+                let mut bindings = Vec::new();
+                if !self.match_pattern(&pattern, &val, &mut bindings)? {
+                    return Err(anyhow!(
+                        "Cannot destructure {} {} with this pattern",
+                        val.type_name(),
+                        val
+                    ));
+                }
+                for (name, bound) in bindings {
+                    self.environment.define(name, bound);
                 }
+                Ok(val)
             }
 
-            _ => Err(anyhow!("Statement type not yet implemented: {:?}", stmt)),
+            Stmt::Break => Err(ControlFlow::Break.into()),
+
+            Stmt::Continue => Err(ControlFlow::Continue.into()),
         }
     }
 
     fn execute_block(&mut self, statements: Vec<Stmt>) -> Result<Value> {
-        let previous = self.environment.clone();
-        self.environment = Environment::new_with_parent(previous.clone());
+        self.execute_block_with(Vec::new(), statements)
+    }
 
-        let mut result = Value::Nil;
+    /// Executes `statements` in a new child scope pre-populated with `bindings`.
+    ///
+    /// The scope is popped even when a statement fails, so assignments to
+    /// outer variables survive and control-flow signals can unwind cleanly.
+    fn execute_block_with(
+        &mut self,
+        bindings: Vec<(String, Value)>,
+        statements: Vec<Stmt>,
+    ) -> Result<Value> {
+        let inner = Environment::new_with_parent(self.environment.clone());
+        let outer = std::mem::replace(&mut self.environment, inner);
+        for (name, value) in bindings {
+            self.environment.define(name, value);
+        }
+
+        let mut result = Ok(Value::Nil);
         for stmt in statements {
-            result = self.execute_statement(stmt)?;
+            result = self.execute_statement(stmt);
+            if result.is_err() {
+                break;
+            }
         }
 
-        self.environment = previous;
-        Ok(result)
+        self.environment = outer;
+        result
+    }
+
+    /// Interprets the outcome of one loop iteration: `Ok(true)` to keep
+    /// looping, `Ok(false)` on `break`, and any other error is propagated.
+    fn continue_loop(result: Result<Value>) -> Result<bool> {
+        match result {
+            Ok(_) => Ok(true),
+            Err(e) => match e.downcast_ref::<ControlFlow>() {
+                Some(ControlFlow::Break) => Ok(false),
+                Some(ControlFlow::Continue) => Ok(true),
+                _ => Err(e),
+            },
+        }
+    }
+
+    /// Interprets the outcome of a function body at the call boundary.
+    ///
+    /// `return` and `?` yield their value; a `break` or `continue` that escaped every
+    /// loop in the body becomes an ordinary error instead of leaking into a
+    /// loop in the caller.
+    fn function_result(result: Result<Value>) -> Result<Value> {
+        match result {
+            Ok(value) => Ok(value),
+            Err(e) => match e.downcast::<ControlFlow>() {
+                Ok(ControlFlow::Return(value) | ControlFlow::Propagate(value)) => Ok(value),
+                Ok(flow) => Err(anyhow!("{}", flow)),
+                Err(e) => Err(e),
+            },
+        }
+    }
+
+    /// Produces the values a `for` loop walks over.
+    ///
+    /// Command substitutions stream their output lines as the command runs,
+    /// strings iterate over their grapheme clusters and maps over their keys
+    /// in sorted order. Ranges and iterators are consumed lazily, and
+    /// channels are received from until they are closed.
+    fn iteration_values(&mut self, iterable: Expr) -> Result<ValueStream> {
+        if let Expr::Command(command) = iterable {
+            return Ok(Box::new(LazyIterator::command_lines(&command)?));
+        }
+        let value = self.evaluate_expression(iterable)?;
+        self.iterate(value)
+    }
+
+    /// Iterates over `value`. A struct is iterable when it has an `iter`
+    /// method, which returns anything iterable, such as a generator.
+    fn iterate(&mut self, value: Value) -> Result<ValueStream> {
+        match value {
+            Value::Array(items) | Value::Tuple(items) => Ok(Box::new(items.into_iter().map(Ok))),
+            Value::Map(map) => {
+                let mut keys: Vec<String> = map.into_keys().collect();
+                keys.sort();
+                Ok(Box::new(keys.into_iter().map(|key| Ok(Value::String(key)))))
+            }
+            Value::String(s) => {
+                let graphemes: Vec<Value> = s
+                    .graphemes(true)
+                    .map(|g| Value::String(g.to_string()))
+                    .collect();
+                Ok(Box::new(graphemes.into_iter().map(Ok)))
+            }
+            Value::Range(range) => Ok(Box::new(range.iter().map(|i| Ok(Value::Integer(i))))),
+            Value::Iterator(iterator) => Ok(Box::new(iterator)),
+            // Receives until the channel is closed and drained
+            Value::Channel(channel) => {
+                Ok(Box::new(std::iter::from_fn(move || channel.recv().map(Ok))))
+            }
+            value @ Value::Struct { .. } => match struct_method(&value, "iter") {
+                Some(iter) => {
+                    let iterable = self.call_value(iter, vec![value].into())?;
+                    self.iterate(iterable)
+                }
+                None => Err(anyhow!(
+                    "Cannot iterate over {}: it has no 'iter' method",
+                    value.type_name()
+                )),
+            },
+            other => Err(anyhow!("Cannot iterate over {}", other.type_name())),
+        }
     }
 
     fn evaluate_expression(&mut self, expr: Expr) -> Result<Value> {
         match expr {
             Expr::Integer(i) => Ok(Value::Integer(i)),
+            Expr::BigInt(i) => Ok(i.into()),
             Expr::Float(f) => Ok(Value::Float(f)),
             Expr::String(s) => Ok(Value::String(s)),
             Expr::Boolean(b) => Ok(Value::Boolean(b)),
             Expr::Nil => Ok(Value::Nil),
 
-            Expr::Identifier(name) => {
-                // Check built-ins first, then environment variables, then external commands
-                if is_builtin(&name) {
+            // Names in scope come first, so a variable may reuse a built-in's
+            // name. A bare built-in runs it, then anything else is a command
+            Expr::Identifier(name) => match self.environment.get(&name) {
+                Some(Value::Function {
+                    name: builtin,
+                    body,
+                    ..
+                }) if builtin == name && body.is_empty() && is_builtin(&name) => {
                     call_builtin(&name, &[])
-                } else if let Some(value) = self.environment.get(&name) {
-                    Ok(value)
-                } else {
-                    // If it's not a built-in or variable, try to execute as external command
-                    self.execute_external_command(&name, &[])
                 }
-            }
+                Some(value) => Ok(value),
+                None if is_builtin(&name) => call_builtin(&name, &[]),
+                None => self.execute_external_command(&name, &[]),
+            },
 
             Expr::Binary {
                 left,
@@ -290,6 +837,9 @@ impl Interpreter {
                 right,
             } => {
                 let left_val = self.evaluate_expression(*left)?;
+                if short_circuits(&operator, &left_val) {
+                    return Ok(left_val);
+                }
                 let right_val = self.evaluate_expression(*right)?;
                 self.apply_binary_operator(operator, left_val, right_val)
             }
@@ -299,55 +849,112 @@ impl Interpreter {
                 self.apply_unary_operator(operator, operand_val)
             }
 
-            Expr::Call { name, args } => {
-                let mut arg_values = Vec::new();
-                for arg in args {
-                    arg_values.push(self.evaluate_expression(arg)?);
+            Expr::Call { callee, args, .. } => match *callee {
+                Expr::Identifier(name) => {
+                    let args = self.evaluate_call_arguments(args)?;
+                    self.call_named(&name, args)
+                }
+                callee => {
+                    let function = self.evaluate_expression(callee)?;
+                    let args = self.evaluate_call_arguments(args)?;
+                    self.call_value(function, args)
                 }
+            },
 
-                if is_builtin(&name) {
-                    // Call built-in function
-                    call_builtin(&name, &arg_values)
-                } else {
-                    // User-defined function
-                    if let Some(Value::Function {
-                        parameters,
-                        body,
-                        closure,
-                        ..
-                    }) = self.environment.get(&name)
-                    {
-                        if parameters.len() != arg_values.len() {
-                            return Err(anyhow!(
-                                "Function {} expects {} arguments, got {}",
-                                name,
-                                parameters.len(),
-                                arg_values.len()
-                            ));
+            Expr::MethodCall {
+                object,
+                method,
+                args,
+            } => {
+                let receiver = self.evaluate_expression(*object)?;
+                let mut args = self.evaluate_call_arguments(args)?;
+                match receiver {
+                    // A function stored in a map is called like a method
+                    Value::Map(map) if matches!(map.get(&method), Some(Value::Function { .. })) => {
+                        self.call_value(map[&method].clone(), args)
+                    }
+                    Value::EnumType(definition) => {
+                        let values = args.into_positional(&method)?;
+                        self.construct_variant(&definition, &method, values)
+                    }
+                    receiver => match struct_method(&receiver, &method) {
+                        Some(function) => {
+                            // `value.method(args)` passes a copy of the instance
+                            // as the first parameter; `Name.method(args)` doesn't
+                            if matches!(receiver, Value::Struct { .. }) {
+                                args.positional.insert(0, receiver);
+                            }
+                            self.call_value(function, args)
                         }
-
-                        // Create new environment for function execution
-                        let previous = self.environment.clone();
-                        self.environment = Environment::new_with_parent(closure);
-
-                        // Bind parameters
-                        for (param, arg_val) in parameters.iter().zip(arg_values.iter()) {
-                            self.environment.define(param.name.clone(), arg_val.clone());
+                        None => {
+                            let values = args.into_positional(&method)?;
+                            call_method(&receiver, &method, &values)
                         }
+                    },
+                }
+            }
 
-                        // Execute function body
-                        let result = self.execute_block(body)?;
-
-                        // Restore previous environment
-                        self.environment = previous;
-                        Ok(result)
-                    } else {
-                        // Try to execute as external command
-                        self.execute_external_command(&name, &arg_values)
+            Expr::Lambda {
+                parameters,
+                return_type,
+                body,
+            } => Ok(Value::Function {
+                name: "<lambda>".to_string(),
+                parameters,
+                return_type,
+                body,
+                closure: self.environment.clone(),
+            }),
+
+            Expr::Pipeline { left, right } => {
+                // `x |> f(a)` calls `f(x, a)`; `x |> f` calls `f(x)`
+                let input = self.evaluate_expression(*left)?;
+                match *right {
+                    Expr::Call { callee, args, .. } => {
+                        let mut args = self.evaluate_call_arguments(args)?;
+                        args.positional.insert(0, input);
+                        match *callee {
+                            Expr::Identifier(name) => self.call_named(&name, args),
+                            callee => {
+                                let function = self.evaluate_expression(callee)?;
+                                self.call_value(function, args)
+                            }
+                        }
+                    }
+                    Expr::Identifier(name) => self.call_named(&name, vec![input].into()),
+                    other => {
+                        let function = self.evaluate_expression(other)?;
+                        self.call_value(function, vec![input].into())
                     }
                 }
             }
 
+            Expr::If {
+                condition,
+                then_block,
+                else_block,
+            } => self.execute_statement(Stmt::If {
+                condition: *condition,
+                then_block,
+                else_block,
+            }),
+
+            Expr::Block(statements) => self.execute_block(statements),
+
+            Expr::StructLiteral { name, fields, .. } => self.construct_struct(&name, fields),
+
+            Expr::Spawn(body) => Ok(self.spawn(body)),
+
+            Expr::Await(expr) => {
+                let value = self.evaluate_expression(*expr)?;
+                await_task(&value)
+            }
+
+            Expr::Propagate(expr) => {
+                let value = self.evaluate_expression(*expr)?;
+                propagate(value)
+            }
+
             Expr::Array(elements) => {
                 let mut values = Vec::new();
                 for element in elements {
@@ -356,6 +963,8 @@ impl Interpreter {
                 Ok(Value::Array(values))
             }
 
+            Expr::Tuple(elements) => Ok(Value::Tuple(self.evaluate_arguments(elements)?)),
+
             Expr::Map(pairs) => {
                 let mut map = HashMap::new();
                 for (key_expr, value_expr) in pairs {
@@ -374,6 +983,59 @@ impl Interpreter {
                 Ok(Value::Map(map))
             }
 
+            Expr::Range {
+                start,
+                end,
+                inclusive,
+                step,
+            } => {
+                let start = self.evaluate_range_bound(*start)?;
+                let end = self.evaluate_range_bound(*end)?;
+                let step = match step {
+                    Some(step) => self.evaluate_range_bound(*step)?,
+                    None => 1,
+                };
+                Ok(Value::Range(Range::new(start, end, step, inclusive)?))
+            }
+
+            Expr::Index { object, index } => {
+                let object = self.evaluate_expression(*object)?;
+                let index = self.evaluate_expression(*index)?;
+                access::index_value(object, index)
+            }
+
+            Expr::FieldAccess { object, field } => {
+                let object = self.evaluate_expression(*object)?;
+                access::field_value(object, field)
+            }
+
+            Expr::OptionalFieldAccess { object, field } => {
+                let object = match self.evaluate_expression(*object)? {
+                    // On a Result or Option, `?.` unwraps as `?` does first,
+                    // so `cfg()?.host` reads `host` of the Ok value
+                    value if value.is_result_or_option() => propagate(value)?,
+                    value => value,
+                };
+                match object {
+                    Value::Nil => Ok(Value::Nil),
+                    Value::Map(map) => Ok(map.get(&field).cloned().unwrap_or(Value::Nil)),
+                    object => access::field_value(object, field),
+                }
+            }
+
+            Expr::Interpolation(parts) => {
+                let mut result = String::new();
+                for part in parts {
+                    match part {
+                        InterpolationPart::Text(text) => result.push_str(&text),
+                        InterpolationPart::Expression(expr) => {
+                            result.push_str(&self.evaluate_expression(*expr)?.to_string())
+                        }
+                    }
+                }
+                Ok(Value::String(result))
+            }
+
             Expr::Command(cmd) => {
                 let output = Command::new("sh")
                     .arg("-c")
@@ -384,8 +1046,78 @@ impl Interpreter {
                 let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
                 Ok(Value::String(stdout))
             }
+        }
+    }
+
+    fn evaluate_arguments(&mut self, args: Vec<Expr>) -> Result<Vec<Value>> {
+        args.into_iter()
+            .map(|arg| self.evaluate_expression(arg))
+            .collect()
+    }
 
-            _ => Err(anyhow!("Expression type not yet implemented: {:?}", expr)),
+    /// Calls a function by name: a function in scope first, so user code can
+    /// shadow built-ins, then a built-in, and finally an external command of
+    /// that name.
+    fn call_named(&mut self, name: &str, args: CallArgs) -> Result<Value> {
+        match self.environment.get(name) {
+            Some(function @ Value::Function { .. }) => self.call_value(function, args),
+            _ if is_builtin(name) => call_builtin(name, &args.into_positional(name)?),
+            _ => self.execute_external_command(name, &args.into_positional(name)?),
+        }
+    }
+
+    /// Calls a function value, such as a lambda or a function read from an
+    /// array or map.
+    fn call_value(&mut self, function: Value, args: CallArgs) -> Result<Value> {
+        let (name, parameters, return_type, body, closure) = match function {
+            Value::Function {
+                name,
+                parameters,
+                return_type,
+                body,
+                closure,
+            } => (name, parameters, return_type, body, closure),
+            other => return Err(anyhow!("Cannot call a value of type {}", other.type_name())),
+        };
+
+        // Built-ins are stored as functions with an empty body
+        if body.is_empty() && is_builtin(&name) {
+            return call_builtin(&name, &args.into_positional(&name)?);
+        }
+
+        // Create new environment for function execution
+        let previous =
+            std::mem::replace(&mut self.environment, Environment::new_with_parent(closure));
+
+        // A generator binds its arguments now but runs its body lazily
+        if contains_yield(&body) {
+            let bound = self.bind_arguments(&name, &parameters, args);
+            let environment = std::mem::replace(&mut self.environment, previous);
+            bound?;
+            return Ok(self.generator(environment, body));
+        }
+
+        // Bind parameters, then execute the body
+        let result = self
+            .bind_arguments(&name, &parameters, args)
+            .and_then(|()| self.execute_block(body));
+
+        // Restore previous environment, even if the body failed
+        self.environment = previous;
+        let value = Self::function_result(result)?;
+        self.enforce_type(value, return_type.as_ref(), || {
+            format!("return value of {}", name)
+        })
+    }
+
+    fn evaluate_range_bound(&mut self, expr: Expr) -> Result<i64> {
+        match self.evaluate_expression(expr)? {
+            Value::Integer(i) => Ok(i),
+            Value::BigInt(i) => Err(anyhow!("Range bound {} does not fit in 64 bits", i)),
+            other => Err(anyhow!(
+                "Range bounds and step must be integers, got {}",
+                other.type_name()
+            )),
         }
     }
 
@@ -395,27 +1127,27 @@ impl Interpreter {
         left: Value,
         right: Value,
     ) -> Result<Value> {
+        // Logical operators return one of their operands, whatever its type
+        if matches!(
+            operator,
+            BinaryOperator::And | BinaryOperator::Or | BinaryOperator::NilCoalesce
+        ) {
+            return Ok(if short_circuits(&operator, &left) {
+                left
+            } else {
+                right
+            });
+        }
+
+        if operator == BinaryOperator::In {
+            return contains(&right, &left).map(Value::Boolean);
+        }
+
         match (left, right) {
-            (Value::Integer(l), Value::Integer(r)) => match operator {
-                BinaryOperator::Add => Ok(Value::Integer(l + r)),
-                BinaryOperator::Subtract => Ok(Value::Integer(l - r)),
-                BinaryOperator::Multiply => Ok(Value::Integer(l * r)),
-                BinaryOperator::Divide => {
-                    if r == 0 {
-                        Err(anyhow!("Division by zero"))
-                    } else {
-                        Ok(Value::Integer(l / r))
-                    }
-                }
-                BinaryOperator::Modulo => Ok(Value::Integer(l % r)),
-                BinaryOperator::Equal => Ok(Value::Boolean(l == r)),
-                BinaryOperator::NotEqual => Ok(Value::Boolean(l != r)),
-                BinaryOperator::Less => Ok(Value::Boolean(l < r)),
-                BinaryOperator::LessEqual => Ok(Value::Boolean(l <= r)),
-                BinaryOperator::Greater => Ok(Value::Boolean(l > r)),
-                BinaryOperator::GreaterEqual => Ok(Value::Boolean(l >= r)),
-                _ => Err(anyhow!("Unsupported operator for integers: {:?}", operator)),
-            },
+            (Value::Integer(l), Value::Integer(r)) => integer::integer_operation(operator, l, r),
+            (Value::BigInt(l), Value::BigInt(r)) => integer::big_operation(operator, l, r),
+            (Value::BigInt(l), Value::Integer(r)) => integer::big_operation(operator, l, r.into()),
+            (Value::Integer(l), Value::BigInt(r)) => integer::big_operation(operator, l.into(), r),
 
             (Value::Float(l), Value::Float(r)) => match operator {
                 BinaryOperator::Add => Ok(Value::Float(l + r)),
@@ -423,7 +1155,7 @@ impl Interpreter {
                 BinaryOperator::Multiply => Ok(Value::Float(l * r)),
                 BinaryOperator::Divide => {
                     if r == 0.0 {
-                        Err(anyhow!("Division by zero"))
+                        Err(ScriptError::new("DivisionByZero", "Division by zero").into())
                     } else {
                         Ok(Value::Float(l / r))
                     }
@@ -434,6 +1166,7 @@ impl Interpreter {
                 BinaryOperator::LessEqual => Ok(Value::Boolean(l <= r)),
                 BinaryOperator::Greater => Ok(Value::Boolean(l > r)),
                 BinaryOperator::GreaterEqual => Ok(Value::Boolean(l >= r)),
+                BinaryOperator::Power => Ok(Value::Float(l.powf(r))),
                 _ => Err(anyhow!("Unsupported operator for floats: {:?}", operator)),
             },
 
@@ -444,6 +1177,26 @@ impl Interpreter {
                 _ => Err(anyhow!("Unsupported operator for strings: {:?}", operator)),
             },
 
+            // `"-" * 40` and `40 * "-"` repeat the string
+            (Value::String(s), Value::Integer(n)) | (Value::Integer(n), Value::String(s))
+                if operator == BinaryOperator::Multiply =>
+            {
+                let count = usize::try_from(n)
+                    .map_err(|_| anyhow!("Cannot repeat a string {} times", n))?;
+                Ok(Value::String(repeat_string(&s, count)?))
+            }
+
+            (Value::Array(mut l), Value::Array(r)) if operator == BinaryOperator::Add => {
+                l.extend(r);
+                Ok(Value::Array(l))
+            }
+
+            // Keys in the right map win
+            (Value::Map(mut l), Value::Map(r)) if operator == BinaryOperator::Add => {
+                l.extend(r);
+                Ok(Value::Map(l))
+            }
+
             (Value::Integer(l), Value::Float(r)) => {
                 self.apply_binary_operator(operator, Value::Float(l as f64), Value::Float(r))
             }
@@ -452,17 +1205,28 @@ impl Interpreter {
                 self.apply_binary_operator(operator, Value::Float(l), Value::Float(r as f64))
             }
 
+            (Value::BigInt(l), Value::Float(r)) => self.apply_binary_operator(
+                operator,
+                Value::Float(integer::to_f64(&l)),
+                Value::Float(r),
+            ),
+
+            (Value::Float(l), Value::BigInt(r)) => self.apply_binary_operator(
+                operator,
+                Value::Float(l),
+                Value::Float(integer::to_f64(&r)),
+            ),
+
             (Value::Boolean(l), Value::Boolean(r)) => match operator {
-                BinaryOperator::And => Ok(Value::Boolean(l && r)),
-                BinaryOperator::Or => Ok(Value::Boolean(l || r)),
                 BinaryOperator::Equal => Ok(Value::Boolean(l == r)),
                 BinaryOperator::NotEqual => Ok(Value::Boolean(l != r)),
                 _ => Err(anyhow!("Unsupported operator for booleans: {:?}", operator)),
             },
 
             (l, r) => match operator {
-                BinaryOperator::Equal => Ok(Value::Boolean(false)), // Different types are never equal
-                BinaryOperator::NotEqual => Ok(Value::Boolean(true)),
+                // Other pairs compare structurally, and values of different types are never equal
+                BinaryOperator::Equal => Ok(Value::Boolean(l == r)),
+                BinaryOperator::NotEqual => Ok(Value::Boolean(l != r)),
                 _ => Err(anyhow!(
                     "Unsupported operation: {} {:?} {}",
                     l.type_name(),
@@ -476,8 +1240,14 @@ impl Interpreter {
     fn apply_unary_operator(&self, operator: UnaryOperator, operand: Value) -> Result<Value> {
         match (operator, operand) {
             (UnaryOperator::Not, val) => Ok(Value::Boolean(!val.is_truthy())),
-            (UnaryOperator::Minus, Value::Integer(i)) => Ok(Value::Integer(-i)),
+            (UnaryOperator::Minus, Value::Integer(i)) => Ok(i
+                .checked_neg()
+                .map(Value::Integer)
+                .unwrap_or_else(|| Value::from(-BigInt::from(i)))),
+            (UnaryOperator::Minus, Value::BigInt(i)) => Ok(Value::from(-i)),
             (UnaryOperator::Minus, Value::Float(f)) => Ok(Value::Float(-f)),
+            (UnaryOperator::BitwiseNot, Value::Integer(i)) => Ok(Value::Integer(!i)),
+            (UnaryOperator::BitwiseNot, Value::BigInt(i)) => Ok(Value::from(!i)),
             (op, val) => Err(anyhow!(
                 "Unsupported unary operation: {:?} {}",
                 op,
@@ -514,32 +1284,56 @@ impl Interpreter {
                 if output.status.success() {
                     Ok(Value::Nil)
                 } else {
-                    let exit_code = output.status.code().unwrap_or(-1);
-                    Err(anyhow!(
-                        "Command '{}' failed with exit code {}",
-                        command,
-                        exit_code
-                    ))
-                }
-            }
-            Err(e) => {
-                // Check if it's a "command not found" type error
-                if e.kind() == std::io::ErrorKind::NotFound {
-                    Err(anyhow!("Command not found: {}", command))
-                } else {
-                    Err(anyhow!("Failed to execute command '{}': {}", command, e))
+                    Err(ScriptError::command_failed(command, output.status.code()).into())
                 }
             }
+            Err(e) => Err(ScriptError::command_spawn(command, &e).into()),
         }
     }
 }
 
+/// Implements `item in collection`: elements of arrays, tuples and ranges,
+/// keys of maps and substrings of strings.
+fn contains(collection: &Value, item: &Value) -> Result<bool> {
+    match (collection, item) {
+        (Value::Array(items) | Value::Tuple(items), item) => Ok(items.contains(item)),
+        (Value::Map(map), Value::String(key)) => Ok(map.contains_key(key)),
+        (Value::String(s), Value::String(part)) => Ok(s.contains(part.as_str())),
+        (Value::Range(range), Value::Integer(n)) => Ok(range.contains(*n)),
+        (Value::Range(_), _) => Ok(false),
+        (collection, item) => Err(anyhow!(
+            "Cannot check for {} in {}",
+            item.type_name(),
+            collection.type_name()
+        )),
+    }
+}
+
+/// Whether `left` is already the result of `left op right`, so the right side
+/// is never evaluated: a falsy `&&`, a truthy `||` or a non-nil `??`.
+fn short_circuits(operator: &BinaryOperator, left: &Value) -> bool {
+    match operator {
+        BinaryOperator::And => !left.is_truthy(),
+        BinaryOperator::Or => left.is_truthy(),
+        BinaryOperator::NilCoalesce => *left != Value::Nil,
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::lexer::Lexer;
     use crate::parser::Parser;
 
+    fn run(source: &str) -> Result<Value> {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse()?;
+        Interpreter::new().interpret(program)
+    }
+
     #[test]
     fn test_simple_arithmetic() {
         let source = "2 + 3 * 4";
@@ -644,4 +1438,1944 @@ mod tests {
         let result = interpreter.interpret(program).unwrap();
         assert_eq!(result, Value::Integer(50));
     }
+
+    #[test]
+    fn test_for_loop_over_array() {
+        let source = r#"
+            let total is 0
+            for n in [1, 2, 3, 4] {
+                total = total + n
+            }
+            total
+        "#;
+        assert_eq!(run(source).unwrap(), Value::Integer(10));
+    }
+
+    #[test]
+    fn test_for_loop_over_map_keys_and_strings() {
+        let source = r#"
+            let keys is ""
+            for k in {"b": 2, "a": 1} {
+                keys = keys + k
+            }
+            for c in "xy" {
+                keys = keys + c
+            }
+            keys
+        "#;
+        assert_eq!(run(source).unwrap(), Value::String("abxy".to_string()));
+    }
+
+    #[test]
+    fn test_for_loop_over_command_output_lines() {
+        let source = r#"
+            let count is 0
+            for line in $(printf 'one\ntwo\nthree') {
+                count = count + 1
+            }
+            count
+        "#;
+        assert_eq!(run(source).unwrap(), Value::Integer(3));
+    }
+
+    #[test]
+    fn test_while_with_break_and_continue() {
+        let source = r#"
+            let i is 0
+            let odd_sum is 0
+            while true {
+                i = i + 1
+                if i > 9 {
+                    break
+                }
+                if i % 2 == 0 {
+                    continue
+                }
+                odd_sum = odd_sum + i
+            }
+            odd_sum
+        "#;
+        assert_eq!(run(source).unwrap(), Value::Integer(25));
+    }
+
+    #[test]
+    fn test_break_only_exits_innermost_loop() {
+        let source = r#"
+            let pairs is 0
+            for a in [1, 2, 3] {
+                for b in [1, 2, 3] {
+                    if b > a {
+                        break
+                    }
+                    pairs = pairs + 1
+                }
+            }
+            pairs
+        "#;
+        assert_eq!(run(source).unwrap(), Value::Integer(6));
+    }
+
+    #[test]
+    fn test_break_outside_loop_fails() {
+        let err = run("break").unwrap_err();
+        assert!(err.to_string().contains("outside of a loop"));
+    }
+
+    #[test]
+    fn test_early_return_from_nested_blocks() {
+        let source = r#"
+            fn first_negative(items) {
+                for n in items {
+                    if n < 0 {
+                        return n
+                    }
+                }
+                return 0
+            }
+            first_negative([3, 1, -4, -1]) * 10 + first_negative([1, 2])
+        "#;
+        assert_eq!(run(source).unwrap(), Value::Integer(-40));
+    }
+
+    #[test]
+    fn test_return_skips_remaining_statements() {
+        let source = r#"
+            fn check(x) {
+                if x > 5 {
+                    return "big"
+                }
+                "small"
+            }
+            check(10) + "/" + check(1)
+        "#;
+        assert_eq!(run(source).unwrap(), Value::String("big/small".to_string()));
+    }
+
+    #[test]
+    fn test_top_level_return_ends_script() {
+        let source = "x is 1\nreturn x + 1\nx is 100\nx";
+        assert_eq!(run(source).unwrap(), Value::Integer(2));
+
+        // An integer becomes the exit status; other values and a script that
+        // simply ends leave it unset
+        let exit_code = |source: &str| {
+            let program = Parser::new(Lexer::new(source).tokenize()).parse().unwrap();
+            let mut interpreter = Interpreter::new();
+            interpreter.interpret(program).unwrap();
+            interpreter.exit_code()
+        };
+        assert_eq!(exit_code(source), Some(2));
+        assert_eq!(exit_code("return \"done\""), None);
+        assert_eq!(exit_code("fn f() {\n    return 4\n}\nf()"), None);
+        // Codes that do not fit in a byte are refused, not truncated
+        for code in ["256", "-1", "4294967296", "2 ** 70"] {
+            let source = format!("return {}", code);
+            let error = run(&source).unwrap_err().to_string();
+            assert!(error.contains("outside 0..=255"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_break_does_not_escape_function() {
+        let source = r#"
+            fn bad() {
+                break
+            }
+            for i in [1, 2] {
+                bad()
+            }
+        "#;
+        let err = run(source).unwrap_err();
+        assert!(err.to_string().contains("'break' outside of a loop"));
+    }
+
+    #[test]
+    fn test_catch_file_not_found_by_kind() {
+        let source = r#"
+            let result is "unset"
+            try {
+                read_file("/nonexistent/shellux/config.json")
+                result = "read"
+            } catch PermissionDenied as e {
+                result = "denied"
+            } catch FileNotFound as e {
+                result = e.kind + " " + e.path
+            }
+            result
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::String("FileNotFound /nonexistent/shellux/config.json".to_string())
+        );
+    }
+
+    #[test]
+    fn test_catch_division_by_zero_and_command_failure() {
+        let source = r#"
+            let caught is ""
+            try {
+                10 / 0
+            } catch DivisionByZero {
+                caught = caught + "div "
+            }
+            try {
+                sh("-c", "exit 3")
+            } catch CommandFailed as e {
+                caught = caught + e.command + ":" + to_string(e.exit_code)
+            }
+            caught
+        "#;
+        assert_eq!(run(source).unwrap(), Value::String("div sh:3".to_string()));
+    }
+
+    #[test]
+    fn test_throw_custom_error_with_fields() {
+        let source = r#"
+            try {
+                throw(error("DeployFailed", "bad host", {"host": "web-1"}))
+            } catch Error as e {
+                e.kind + "/" + e.message + "/" + e.host
+            }
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::String("DeployFailed/bad host/web-1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_unmatched_catch_rethrows() {
+        let source = r#"
+            try {
+                throw("boom")
+            } catch FileNotFound {
+                1
+            }
+        "#;
+        let err = run(source).unwrap_err();
+        assert_eq!(err.to_string(), "boom");
+    }
+
+    #[test]
+    fn test_return_passes_through_try() {
+        let source = r#"
+            fn find() {
+                try {
+                    return "early"
+                } catch {
+                    return "caught"
+                }
+                "late"
+            }
+            find()
+        "#;
+        assert_eq!(run(source).unwrap(), Value::String("early".to_string()));
+    }
+
+    #[test]
+    fn test_match_literals_ranges_and_alternatives() {
+        let source = r#"
+            fn describe(code) {
+                match code {
+                    0 => "ok"
+                    1 | 2 => "warning"
+                    200..=299 => "http success"
+                    n if n < 0 => "negative " + to_string(n)
+                    _ => "other"
+                }
+            }
+            describe(0) + "," + describe(2) + "," + describe(204) + "," + describe(-3) + "," + describe(99)
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::String("ok,warning,http success,negative -3,other".to_string())
+        );
+    }
+
+    #[test]
+    fn test_match_array_destructuring_with_rest() {
+        let source = r#"
+            match [1, 2, 3, 4] {
+                [] -> { "empty" }
+                [first, ..rest] if len(rest) > 5 -> { "long" }
+                [first, .., last] -> { first * 10 + last }
+            }
+        "#;
+        assert_eq!(run(source).unwrap(), Value::Integer(14));
+
+        let source = r#"
+            match [1, 2, 3] {
+                [_, ..rest] => len(rest),
+            }
+        "#;
+        assert_eq!(run(source).unwrap(), Value::Integer(2));
+    }
+
+    #[test]
+    fn test_match_map_patterns() {
+        let source = r#"
+            let response is {"status": 404, "body": {"error": "missing"}}
+            match response {
+                {"status": 200, "body": body} => body
+                {"status": 400..500, "body": {"error": message}} => "client error: " + message
+                _ => "unknown"
+            }
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::String("client error: missing".to_string())
+        );
+    }
+
+    #[test]
+    fn test_match_bindings_are_scoped_to_arm() {
+        let source = r#"
+            let x is "outer"
+            match 5 {
+                x => x * 2
+            }
+            x
+        "#;
+        assert_eq!(run(source).unwrap(), Value::String("outer".to_string()));
+    }
+
+    #[test]
+    fn test_string_interpolation() {
+        let source = r#"
+            let name is "World"
+            let items is [1, 2]
+            let message is "Hello, ${name}! ${len(items) * 10}% done, many=${len(items) > 1}"
+            message + ' ${raw}'
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::String("Hello, World! 20% done, many=true ${raw}".to_string())
+        );
+    }
+
+    #[test]
+    fn test_triple_quoted_interpolation() {
+        let source =
+            "let table is \"users\"\nlet sql is \"\"\"\nSELECT * FROM ${table}\n\"\"\"\nsql";
+        assert_eq!(
+            run(source).unwrap(),
+            Value::String("\nSELECT * FROM users\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_range_iteration_with_step_and_inclusive_end() {
+        let source = r#"
+            let out is ""
+            for i in 0..3 {
+                out = out + to_string(i)
+            }
+            for i in 1..=9 step 4 {
+                out = out + "," + to_string(i)
+            }
+            for i in 10..0 step -5 {
+                out = out + ";" + to_string(i)
+            }
+            for i in range(2, 4) {
+                out = out + "|" + to_string(i)
+            }
+            out
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::String("012,1,5,9;10;5|2|3".to_string())
+        );
+    }
+
+    #[test]
+    fn test_range_is_lazy() {
+        let source = r#"
+            let huge is 0..9000000000000000000
+            let seen is 0
+            for i in huge {
+                if i == 3 {
+                    break
+                }
+                seen = seen + 1
+            }
+            [len(huge), huge[-1], (0..10 step 3)[2], seen]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::Integer(9000000000000000000),
+                Value::Integer(8999999999999999999),
+                Value::Integer(6),
+                Value::Integer(3),
+            ])
+        );
+
+        // Lengths beyond i64 are exact, never capped or wrapped
+        let min = "(-9223372036854775807 - 1)";
+        assert_eq!(
+            run(&format!("len({}..9223372036854775807)", min)).unwrap(),
+            Value::BigInt(BigInt::from(u64::MAX))
+        );
+        assert_eq!(
+            run(&format!("len({}..=9223372036854775807)", min)).unwrap(),
+            Value::BigInt(BigInt::from(u64::MAX) + 1)
+        );
+    }
+
+    #[test]
+    fn test_range_errors() {
+        assert!(run("0..10 step 0").is_err());
+        assert!(run("(0..3)[3]").is_err());
+        assert!(run("0..2.5").is_err());
+    }
+
+    #[test]
+    fn test_pipeline_calls() {
+        let source = r#"
+            fn double(x) {
+                return x * 2
+            }
+            fn add(x, y) {
+                return x + y
+            }
+            let result is 5 |> double() |> add(1)
+            let bare is 4 |> double
+            [result, bare, "hello" |> len()]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::Integer(11),
+                Value::Integer(8),
+                Value::Integer(5),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_pipeline_across_lines() {
+        let source = r#"
+            fn inc(x) {
+                return x + 1
+            }
+            let n is 1
+                |> inc()
+                |> inc()
+
+                |> inc()
+            n
+        "#;
+        assert_eq!(run(source).unwrap(), Value::Integer(4));
+        assert!(run("1 |> 2").is_err());
+    }
+
+    #[test]
+    fn test_index_and_field_reads() {
+        let source = r#"
+            let items is [10, 20, 30]
+            let cfg is {"db": {"host": "localhost", "ports": [5432, 5433]}}
+            [items[0], items[-1], "héllo"[1], cfg["db"]["host"], cfg.db.ports[-1]]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::Integer(10),
+                Value::Integer(30),
+                Value::String("é".to_string()),
+                Value::String("localhost".to_string()),
+                Value::Integer(5433),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_nested_index_assignment() {
+        let source = r#"
+            let cfg is {"db": {"port": 1, "tags": ["a", "b"]}}
+            cfg["db"]["port"] = 5432
+            cfg.db.tags[-1] = "z"
+            cfg.name = "main"
+            [cfg["db"]["port"], cfg.db.tags[1], cfg.name]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::Integer(5432),
+                Value::String("z".to_string()),
+                Value::String("main".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_compound_assignment() {
+        let source = r#"
+            let x is 10
+            x += 5
+            x -= 3
+            x *= 4
+            x /= 6
+            x %= 5
+            let counts is {"a": 1}
+            counts["a"] += 2
+            let items is [1, 2]
+            items[0] *= 10
+            [x, counts.a, items[0]]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::Integer(3),
+                Value::Integer(3),
+                Value::Integer(10),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_index_errors() {
+        let source = r#"
+            let items is [1, 2]
+            try {
+                items[5] = 1
+            } catch IndexError as e {
+                e.index
+            }
+        "#;
+        assert_eq!(run(source).unwrap(), Value::Integer(5));
+        assert!(run(r#"let m is {"a": 1}
+m["b"] += 1"#)
+        .is_err());
+        assert!(run("const xs is [1]\nxs[0] = 2").is_err());
+    }
+
+    #[test]
+    fn test_lambdas() {
+        let source = r#"
+            let double is fn(x) { x * 2 }
+            let add is (a, b) => a + b
+            let inc is n => n + 1
+            let shout is fn(s) s + "!"
+            [double(4), add(2, 3), inc(9), shout("hi"), (() => 7)()]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::Integer(8),
+                Value::Integer(5),
+                Value::Integer(10),
+                Value::String("hi!".to_string()),
+                Value::Integer(7),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_call_any_expression() {
+        let source = r#"
+            fn make_adder(n) {
+                return x => x + n
+            }
+            let handlers is {"up": s => s + "^", "len": s => len(s)}
+            let ops is [fn(x) x - 1, make_adder(10)]
+            let name is "up"
+            [make_adder(1)(2), handlers[name]("a"), handlers["len"]("abc"), ops[-1](5), 3 |> ops[0]]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::Integer(3),
+                Value::String("a^".to_string()),
+                Value::Integer(3),
+                Value::Integer(15),
+                Value::Integer(2),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_lambda_errors_and_guards() {
+        assert!(run("let x is 5\nx(1)").is_err());
+        assert!(run("(a => a)(1, 2)").is_err());
+
+        let source = r#"
+            let limit is 3
+            match 5 {
+                n if n > limit => "big"
+                _ => "small"
+            }
+        "#;
+        assert_eq!(run(source).unwrap(), Value::String("big".to_string()));
+    }
+
+    #[test]
+    fn test_functions_share_enclosing_scope() {
+        let source = r#"
+            let total is 0
+            fn add(n) {
+                total += n
+            }
+            add(2)
+            add(3)
+            if total > 0 {
+                total = total * 10
+            }
+            total
+        "#;
+        assert_eq!(run(source).unwrap(), Value::Integer(50));
+    }
+
+    #[test]
+    fn test_recursion() {
+        let source = r#"
+            fn fact(n) {
+                if n <= 1 {
+                    return 1
+                }
+                return n * fact(n - 1)
+            }
+            fact(10)
+        "#;
+        assert_eq!(run(source).unwrap(), Value::Integer(3628800));
+    }
+
+    #[test]
+    fn test_closures_capture_by_reference() {
+        let source = r#"
+            fn counter() {
+                let count is 0
+                return fn() {
+                    count += 1
+                    count
+                }
+            }
+            let a is counter()
+            let b is counter()
+            a()
+            a()
+            b()
+            let late is () => later
+            let later is "seen"
+            [a(), b(), late()]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::Integer(3),
+                Value::Integer(2),
+                Value::String("seen".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_string_methods() {
+        let strings = |items: &[&str]| {
+            Value::Array(items.iter().map(|s| Value::String(s.to_string())).collect())
+        };
+        let string = |s: &str| Value::String(s.to_string());
+        let cases = vec![
+            (r#""  Hello World  ".trim().lower()"#, string("hello world")),
+            (r#""x".upper()"#, string("X")),
+            (r#""a,b,,c".split(",")"#, strings(&["a", "b", "", "c"])),
+            (r#""one  two".split()"#, strings(&["one", "two"])),
+            (r#""a,b".replace(",", ";")"#, string("a;b")),
+            (r#""Hello".starts_with("He")"#, Value::Boolean(true)),
+            (r#""file.log".ends_with(".txt")"#, Value::Boolean(false)),
+            (r#""Hello World".contains("lo W")"#, Value::Boolean(true)),
+            (r#""l1\nl2".lines()"#, strings(&["l1", "l2"])),
+            (r#""7".pad_left(3, "0")"#, string("007")),
+            (r#""ab".pad_right(4) + "|""#, string("ab  |")),
+            (r#""ab".repeat(3)"#, string("ababab")),
+            (r#""héllo".find("l")"#, Value::Integer(2)),
+            (r#""abc".find("z")"#, Value::Nil),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(run(source).unwrap(), expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_method_dispatch_and_introspection() {
+        let source = r#"
+            let obj is {"greet": name => "hi " + name}
+            let names is methods("")
+            [obj.greet("bo"), len(names), names[0], len(methods(1))]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::String("hi bo".to_string()),
+                Value::Integer(18),
+                Value::String("bytes".to_string()),
+                Value::Integer(0),
+            ])
+        );
+        assert!(run(r#""abc".shout()"#).is_err());
+        assert!(run(r#""abc".repeat(-1)"#).is_err());
+        assert!(run(r#""abc".trim(1)"#).is_err());
+
+        // Huge results are refused with a catchable error, not a crash
+        for call in [
+            "repeat(9223372036854775807)",
+            "repeat(2 ** 40)",
+            "pad_left(9223372036854775807)",
+            "pad_right(2 ** 40, \"*\")",
+        ] {
+            let source = format!(
+                "try {{\n    \"ab\".{}\n}} catch OverflowError as e {{\n    e.message\n}}",
+                call
+            );
+            assert_eq!(
+                run(&source).unwrap(),
+                Value::String("String result is larger than 1073741824 bytes".to_string()),
+                "{}",
+                call
+            );
+        }
+    }
+
+    #[test]
+    fn test_runtime_type_annotations() {
+        let source = r#"
+            let port: int is 8080
+            let ratio: float is 2
+            let names: []string is ["a", "b"]
+            let limits: map[string]float is {"cpu": 1, "mem": 0.5}
+            fn add(a: int, b: int) -> int {
+                return a + b
+            }
+            [add(port, 1), ratio, names, limits["cpu"]]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::Integer(8081),
+                Value::Float(2.0),
+                Value::Array(vec![
+                    Value::String("a".to_string()),
+                    Value::String("b".to_string()),
+                ]),
+                Value::Float(1.0),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_runtime_type_errors() {
+        let error_message = |source: &str| run(source).unwrap_err().to_string();
+        assert_eq!(
+            error_message(r#"let port: int is "abc""#),
+            "variable 'port' must be int, got string"
+        );
+        assert_eq!(
+            error_message("fn add(a: int, b: int) {\n    return a + b\n}\nadd(\"x\", \"y\")"),
+            "argument 'a' to add must be int, got string"
+        );
+        assert_eq!(
+            error_message("fn name() -> string {\n    return 1\n}\nname()"),
+            "return value of name must be string, got int"
+        );
+        assert_eq!(
+            error_message(r#"const ids: []int is [1, "2"]"#),
+            "constant 'ids' must be []int, got array"
+        );
+
+        // Declared types hold for later assignments, not just the first
+        assert_eq!(
+            error_message("let port: int is 8080\nport = \"abc\""),
+            "variable 'port' must be int, got string"
+        );
+        assert_eq!(
+            error_message("fn f(a: int) {\n    a = \"x\"\n}\nf(1)"),
+            "variable 'a' must be int, got string"
+        );
+        assert_eq!(
+            error_message("let ids: []int is [1]\nids[0] = \"2\""),
+            "variable 'ids' must be []int, got array"
+        );
+        let source = "let ratio: float is 1\nratio += 1\nx is ratio\nratio = 3\n[x, ratio]";
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![Value::Float(2.0), Value::Float(3.0)])
+        );
+
+        let source = r#"
+            try {
+                let m: map[string]int is {"a": "b"}
+            } catch TypeError as e {
+                e.kind
+            }
+        "#;
+        assert_eq!(run(source).unwrap(), Value::String("TypeError".to_string()));
+
+        // Only an error may be nil; struct types need a struct
+        let source = "struct Point {\n    x: int\n}\nlet p: Point is nil";
+        assert_eq!(error_message(source), "variable 'p' must be Point, got nil");
+    }
+
+    #[test]
+    fn test_runtime_types_can_be_disabled() {
+        let run_with = |source: &str, runtime_types: bool| {
+            let tokens = Lexer::new(source).tokenize();
+            let program = Parser::new(tokens).parse().unwrap();
+            let mut interpreter = Interpreter::new();
+            interpreter.set_runtime_types(runtime_types);
+            interpreter.interpret(program)
+        };
+        assert_eq!(
+            run_with(r#"let port: int is "abc""#, false).unwrap(),
+            Value::String("abc".to_string())
+        );
+        // Only the check is skipped: ints are still widened where floats are
+        // declared, so results are the same with the flag on or off
+        let source = r#"
+            fn half(x: float) -> float {
+                return x / 2
+            }
+            let f: float is 1
+            f = 2
+            [f / 4, half(3)]
+        "#;
+        let expected = Value::Array(vec![Value::Float(0.5), Value::Float(1.5)]);
+        assert_eq!(run_with(source, true).unwrap(), expected);
+        assert_eq!(run_with(source, false).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_multiple_return_values() {
+        let source = r#"
+            fn divide(a: float, b: float) -> (float, error) {
+                if b == 0 {
+                    return 0.0, error("division by zero")
+                }
+                return a / b, nil
+            }
+            message := "ok"
+            result, err := divide(10.0, 4.0)
+            _, failure := divide(1.0, 0)
+            pair is (1, "one")
+            [result, err == nil, failure != nil, failure.message, pair[1], len(pair), message]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::Float(2.5),
+                Value::Boolean(true),
+                Value::Boolean(true),
+                Value::String("division by zero".to_string()),
+                Value::String("one".to_string()),
+                Value::Integer(2),
+                Value::String("ok".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_destructuring_declarations() {
+        let source = r#"
+            first, ..rest := [1, 2, 3]
+            [x, y] := [10, 20]
+            {name, "port": port} := {"name": "db", "port": 5432}
+            a, b is (true, false)
+            [first, rest, x + y, name, port, a, b]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::Integer(1),
+                Value::Array(vec![Value::Integer(2), Value::Integer(3)]),
+                Value::Integer(30),
+                Value::String("db".to_string()),
+                Value::Integer(5432),
+                Value::Boolean(true),
+                Value::Boolean(false),
+            ])
+        );
+        assert!(run("a, b := (1, 2, 3)").is_err());
+        assert!(run(r#"{missing} := {"other": 1}"#).is_err());
+        assert_eq!(run("nil == nil").unwrap(), Value::Boolean(true));
+    }
+
+    #[test]
+    fn test_if_and_block_expressions() {
+        let source = r#"
+            fn grade(score) {
+                if score >= 90 {
+                    "A"
+                } else if score >= 80 {
+                    "B"
+                } else {
+                    "C"
+                }
+            }
+            n := 12
+            level := if n > 10 { "high" } else { "low" }
+            area := {
+                width := 3
+                width * 4
+            }
+            [grade(95), grade(85), grade(10), level, area, if false { 1 }]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::String("A".to_string()),
+                Value::String("B".to_string()),
+                Value::String("C".to_string()),
+                Value::String("high".to_string()),
+                Value::Integer(12),
+                Value::Nil,
+            ])
+        );
+        // Names declared inside a block expression do not leak out
+        assert!(run("x := { inner := 1\n inner }\ninner").is_err());
+    }
+
+    /// Writes `files` into a fresh directory and runs `main.sx` from it.
+    fn run_files(test: &str, files: &[(&str, &str)]) -> Result<Value> {
+        with_files(test, files, |interpreter, program| {
+            interpreter.interpret(program)
+        })?
+    }
+
+    /// Writes `files` to a temporary directory and hands `main.sx`, parsed,
+    /// to `run` with an interpreter set up to import the rest.
+    fn with_files<T>(
+        test: &str,
+        files: &[(&str, &str)],
+        run: impl FnOnce(&mut Interpreter, Program) -> T,
+    ) -> Result<T> {
+        let dir = std::env::temp_dir().join(format!("shellux-{}-{}", test, std::process::id()));
+        for (name, source) in files {
+            let path = dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(path, source)?;
+        }
+        let source = std::fs::read_to_string(dir.join("main.sx"))?;
+        let program = Parser::new(Lexer::new(&source).tokenize()).parse()?;
+        let mut interpreter = Interpreter::new();
+        interpreter.set_script_path(dir.join("main.sx"));
+        let result = run(&mut interpreter, program);
+        std::fs::remove_dir_all(&dir)?;
+        Ok(result)
+    }
+
+    #[test]
+    fn test_imports() {
+        let git = r#"
+            fn run_git(args) {
+                return "git " + args
+            }
+            pub fn status() {
+                return run_git("status")
+            }
+            pub const VERSION is 2
+            hidden := 1
+        "#;
+        let util = r#"
+            count := 0
+            pub fn bump() {
+                count += 1
+                return count
+            }
+            pub fn retry(n) { return n * 2 }
+        "#;
+        let main = r#"
+            import "lib/git.sx" as git
+            import "lib/util.sx"
+            from "lib/../lib/util.sx" import retry, bump
+            [git.status(), git.VERSION, retry(4), util.bump(), bump(), len(git)]
+        "#;
+        let files = [
+            ("main.sx", main),
+            ("lib/git.sx", git),
+            ("lib/util.sx", util),
+        ];
+        assert_eq!(
+            run_files("imports", &files).unwrap(),
+            Value::Array(vec![
+                Value::String("git status".to_string()),
+                Value::Integer(2),
+                Value::Integer(8),
+                // Both imports share the one cached module instance
+                Value::Integer(1),
+                Value::Integer(2),
+                Value::Integer(2),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let private = [
+            ("main.sx", "from \"lib.sx\" import hidden"),
+            ("lib.sx", "hidden := 1"),
+        ];
+        let err = run_files("import-private", &private).unwrap_err();
+        assert_eq!(err.to_string(), "Module 'lib.sx' does not export 'hidden'");
+
+        let missing = [("main.sx", "import \"nowhere.sx\"")];
+        let err = run_files("import-missing", &missing).unwrap_err();
+        assert_eq!(err.to_string(), "Module not found: 'nowhere.sx'");
+
+        let cycle = [
+            ("main.sx", "import \"a.sx\""),
+            ("a.sx", "import \"b.sx\""),
+            ("b.sx", "import \"main.sx\""),
+        ];
+        let err = run_files("import-cycle", &cycle).unwrap_err();
+        assert!(err.to_string().starts_with("Cyclic import: "));
+        assert!(err.to_string().ends_with("main.sx"));
+    }
+
+    #[test]
+    fn test_generators_share_the_module_cache() {
+        let counter = r#"
+            count := 0
+            pub fn bump() {
+                count += 1
+                return count
+            }
+        "#;
+        let main = r#"
+            import "counter.sx"
+            fn counts() {
+                import "counter.sx" as again
+                yield again.bump()
+            }
+            [counter.bump(), counts().collect(), counter.bump()]
+        "#;
+        let files = [("main.sx", main), ("counter.sx", counter)];
+        assert_eq!(
+            run_files("generator-imports", &files).unwrap(),
+            Value::Array(vec![
+                Value::Integer(1),
+                Value::Array(vec![Value::Integer(2)]),
+                Value::Integer(3),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_tasks_share_the_module_cache() {
+        let counter = r#"
+            count := 0
+            pub fn bump() {
+                count += 1
+                return count
+            }
+        "#;
+        let main = r#"
+            import "counter.sx"
+            first := counter.bump()
+            task := spawn {
+                import "counter.sx" as again
+                again.bump()
+            }
+            [first, await task, counter.bump()]
+        "#;
+        let files = [("main.sx", main), ("counter.sx", counter)];
+        assert_eq!(
+            run_files("task-imports", &files).unwrap(),
+            Value::Array(vec![
+                Value::Integer(1),
+                Value::Integer(2),
+                Value::Integer(3),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_module_return_does_not_set_exit_code() {
+        let files = [
+            ("main.sx", "import \"lib.sx\" as lib\nlib.VERSION"),
+            ("lib.sx", "pub const VERSION is 2\nreturn 3"),
+        ];
+        let (result, exit_code) = with_files("import-return", &files, |interpreter, program| {
+            (interpreter.interpret(program), interpreter.exit_code())
+        })
+        .unwrap();
+        assert_eq!(result.unwrap(), Value::Integer(2));
+        assert_eq!(exit_code, None);
+    }
+
+    #[test]
+    fn test_structs() {
+        let source = r#"
+            struct Server {
+                host: string,
+                port: int
+            }
+            impl Server {
+                fn new(host: string) -> Server {
+                    return Server { host: host, port: 22 }
+                }
+                fn url(self) -> string {
+                    return "${self.host}:${self.port}"
+                }
+            }
+            s := Server.new("db")
+            before := s.url()
+            s.port = 8080
+            s.port += 1
+            [before, s.url(), s.port, s == Server { port: 8081, host: "db" }]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::String("db:22".to_string()),
+                Value::String("db:8081".to_string()),
+                Value::Integer(8081),
+                Value::Boolean(true),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_methods_receive_a_copy_of_self() {
+        let source = r#"
+            struct Counter {
+                count: int
+            }
+            impl Counter {
+                fn bump(self) {
+                    self.count += 1
+                    return self.count
+                }
+                fn bumped(self) -> Counter {
+                    self.count += 1
+                    return self
+                }
+            }
+            c := Counter { count: 0 }
+            inside := c.bump()
+            before := c.count
+            c = c.bumped()
+            [inside, before, c.count]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::Integer(1),
+                Value::Integer(0),
+                Value::Integer(1)
+            ])
+        );
+    }
+
+    #[test]
+    fn test_struct_errors() {
+        let setup = "struct Point { x: int, y: int }\n";
+        let error = |source: &str| {
+            run(&format!("{}{}", setup, source))
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(error("Point { x: 1 }"), "Missing field 'y' in Point");
+        assert_eq!(
+            error("Point { x: 1, y: 2, z: 3 }"),
+            "Point has no field 'z'"
+        );
+        assert_eq!(
+            error("Point { x: 1, y: \"2\" }"),
+            "field 'y' of Point must be int, got string"
+        );
+        assert_eq!(
+            error("p := Point { x: 1, y: 2 }\np.x = 1.5"),
+            "field 'x' of Point must be int, got float"
+        );
+        assert_eq!(
+            error("p := Point { x: 1, y: 2 }\np.z"),
+            "Point has no field 'z'"
+        );
+        assert_eq!(
+            error("p := Point { x: 1, y: 2 }\np.norm()"),
+            "Point has no method 'norm'"
+        );
+    }
+
+    #[test]
+    fn test_enums() {
+        let source = r#"
+            enum Shape {
+                Circle(float),
+                Rect(float, float),
+                Empty
+            }
+            fn area(shape: Shape) -> float {
+                match shape {
+                    Circle(r) => 3.0 * r * r
+                    Shape.Rect(w, h) => w * h
+                    Empty => 0.0
+                }
+            }
+            [area(Shape.Circle(1)), area(Shape.Rect(2.0, 3.0)), area(Shape.Empty)]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::Float(3.0),
+                Value::Float(6.0),
+                Value::Float(0.0)
+            ])
+        );
+
+        let setup = "enum Shape { Circle(float), Empty }\n";
+        let error = |source: &str| {
+            run(&format!("{}{}", setup, source))
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(error("Shape.Square(1)"), "Shape has no variant 'Square'");
+        assert_eq!(
+            error("Shape.Circle(1.0, 2.0)"),
+            "Shape.Circle takes 1 value, got 2"
+        );
+        assert_eq!(
+            error("Shape.Circle(\"big\")"),
+            "value 1 of Shape.Circle must be float, got string"
+        );
+        assert_eq!(
+            error("Shape.Circle"),
+            "Shape.Circle takes a value; construct it with Shape.Circle(...)"
+        );
+    }
+
+    #[test]
+    fn test_result_and_option() {
+        let source = r#"
+            fn parse_port(text: string) -> Result {
+                if text == "" {
+                    return Err("empty port")
+                }
+                return Ok(len(text) * 1000)
+            }
+            fn first_port(texts) {
+                port := parse_port(texts[0])?
+                return Ok(port + 1)
+            }
+            fn lookup(items, i) {
+                return if i < len(items) { Some(items[i]) } else { None }
+            }
+            fn describe(result) {
+                match result {
+                    Ok(port) => "port ${port}"
+                    Err(message) => "failed: ${message}"
+                }
+            }
+            [describe(first_port(["80"])), describe(first_port([""])), lookup([1], 5), lookup([1], 0)]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::String("port 2001".to_string()),
+                Value::String("failed: empty port".to_string()),
+                Value::builtin_variant("None", vec![]),
+                Value::builtin_variant("Some", vec![Value::Integer(1)]),
+            ])
+        );
+        assert_eq!(
+            run("fn f() { return 1? }\nf()").unwrap_err().to_string(),
+            "'?' expects a Result or Option, got int"
+        );
+
+        // Outside a function there is nothing to return to, so an `Err` or
+        // `None` that reaches `?` is an error rather than a quiet exit
+        let cases = [
+            (
+                "v is Err(\"bad\")?\nv",
+                "Unhandled Err(bad) from '?' outside of a function",
+            ),
+            (
+                "v is None?\nv",
+                "Unhandled None from '?' outside of a function",
+            ),
+            (
+                "await spawn { Err(\"late\")? }",
+                "Unhandled Err(late) from '?' outside of a function",
+            ),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(run(source).unwrap_err().to_string(), expected, "{}", source);
+        }
+        assert_eq!(run("v is Some(2)?\nv").unwrap(), Value::Integer(2));
+    }
+
+    #[test]
+    fn test_short_circuit_operators() {
+        let source = r#"
+            calls := 0
+            fn touch(value) {
+                calls += 1
+                return value
+            }
+            skipped := [false && touch(1), true || touch(2), 5 ?? touch(3)]
+            [skipped, calls, nil || "default", 0 && touch(4), 1 && "last", nil ?? false ?? 1]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::Array(vec![
+                    Value::Boolean(false),
+                    Value::Boolean(true),
+                    Value::Integer(5)
+                ]),
+                Value::Integer(0),
+                Value::String("default".to_string()),
+                Value::Integer(0),
+                Value::String("last".to_string()),
+                Value::Boolean(false),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_optional_chaining() {
+        let source = r#"
+            config := {"db": {"host": "localhost"}, "cache": nil}
+            [config?.db?.host, config?.cache?.host, config?.queue?.host ?? "none"]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::String("localhost".to_string()),
+                Value::Nil,
+                Value::String("none".to_string()),
+            ])
+        );
+        // Plain field access still reports missing keys
+        assert!(run("config := {\"a\": 1}\nconfig.missing ?? 1").is_err());
+        // On a Result or Option, `?.` unwraps or propagates before reading
+        let source = r#"
+            struct Server {
+                host: string
+            }
+            fn cfg(ok) {
+                if ok {
+                    return Ok(Server { host: "example.com" })
+                }
+                return Err("no config")
+            }
+            fn host(ok) {
+                return Ok(cfg(ok)?.host)
+            }
+            [host(true), host(false), Some({"port": 80})?.port]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::builtin_variant("Ok", vec![Value::String("example.com".to_string())]),
+                Value::builtin_variant("Err", vec![Value::String("no config".to_string())]),
+                Value::Integer(80),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_power_and_bitwise_operators() {
+        let source = r#"
+            flags := 5 | 8
+            [2 ** 10, -2 ** 2, 2 ** 3 ** 2, 2 ** -1, 1.5 ** 2, flags & 4, flags ^ 15, ~0, 1 << 4, -16 >> 2]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::Integer(1024),
+                Value::Integer(-4),
+                Value::Integer(512),
+                Value::Float(0.5),
+                Value::Float(2.25),
+                Value::Integer(4),
+                Value::Integer(2),
+                Value::Integer(-1),
+                Value::Integer(16),
+                Value::Integer(-4),
+            ])
+        );
+        // Shifts of 64 places or more grow into big integers or run out of bits
+        assert_eq!(
+            run("[to_string(1 << 64), to_string(10 ** 20 << 100 >> 100), -1 >> 100, 5 >> 64, 10 ** 20 >> 1000, -(10 ** 20) >> 1000, 0 << 10 ** 30]").unwrap(),
+            Value::Array(vec![
+                Value::String("18446744073709551616".to_string()),
+                Value::String("100000000000000000000".to_string()),
+                Value::Integer(-1),
+                Value::Integer(0),
+                Value::Integer(0),
+                Value::Integer(-1),
+                Value::Integer(0),
+            ])
+        );
+        assert_eq!(
+            run("try {\n1 << 2 ** 21\n} catch OverflowError as e {\n\"caught\"\n}").unwrap(),
+            Value::String("caught".to_string())
+        );
+        assert_eq!(
+            run("try {\n1 >> -1\n} catch ValueError as e {\n\"caught\"\n}").unwrap(),
+            Value::String("caught".to_string())
+        );
+    }
+
+    #[test]
+    fn test_mixed_type_arithmetic_and_membership() {
+        let source = r#"
+            merged := {"a": 1, "b": 2} + {"b": 3}
+            ["-" * 3, 2 * "ab", [1, 2] + [3], merged.b, 2 in [1, 2], "b" in merged, "ell" in "hello", 4 in 0..10 step 2, 5 in 0..10 step 2, 10 in 0..10]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::String("---".to_string()),
+                Value::String("abab".to_string()),
+                Value::Array(vec![
+                    Value::Integer(1),
+                    Value::Integer(2),
+                    Value::Integer(3)
+                ]),
+                Value::Integer(3),
+                Value::Boolean(true),
+                Value::Boolean(true),
+                Value::Boolean(true),
+                Value::Boolean(true),
+                Value::Boolean(false),
+                Value::Boolean(false),
+            ])
+        );
+        assert!(run("\"-\" * -1").is_err());
+        assert!(run("1 in 5").is_err());
+
+        let source = r#"
+            try {
+                "ab" * 9223372036854775807
+            } catch OverflowError as e {
+                e.message
+            }
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::String("String result is larger than 1073741824 bytes".to_string())
+        );
+    }
+
+    #[test]
+    fn test_integer_overflow_promotes_to_big_integers() {
+        let source = r#"
+            max := 9223372036854775807
+            min := -max - 1
+            big := max + 1
+            [to_string(big), to_string(max * max), to_string(2 ** 64), to_string(min / -1), min % -1, to_string(-min), big - 1 == max, big > max, to_string(big * 1.0), to_string(1 << 63), to_int("123456789012345678901234567890") % 1000]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::String("9223372036854775808".to_string()),
+                Value::String("85070591730234615847396907784232501249".to_string()),
+                Value::String("18446744073709551616".to_string()),
+                Value::String("9223372036854775808".to_string()),
+                Value::Integer(0),
+                Value::String("9223372036854775808".to_string()),
+                Value::Boolean(true),
+                Value::Boolean(true),
+                Value::String("9223372036854776000".to_string()),
+                Value::String("9223372036854775808".to_string()),
+                Value::Integer(890),
+            ])
+        );
+        // Results that fit again come back as plain integers
+        assert_eq!(
+            run("big := 2 ** 70\nbig / 2 ** 60").unwrap(),
+            Value::Integer(1024)
+        );
+        // Literals beyond i64 are big integers too
+        assert_eq!(
+            run("[9223372036854775808 == 2 ** 63, to_string(-100000000000000000000), -9223372036854775808 == -9223372036854775807 - 1]").unwrap(),
+            Value::Array(vec![
+                Value::Boolean(true),
+                Value::String("-100000000000000000000".to_string()),
+                Value::Boolean(true),
+            ])
+        );
+        assert!(matches!(
+            run("to_float(-(2 ** 2000))").unwrap(),
+            Value::Float(f) if f == f64::NEG_INFINITY
+        ));
+        // Big integers are out of bounds as indexes, not the wrong type
+        let source = r#"
+            try {
+                [1, 2][10 ** 20]
+            } catch IndexError as e {
+                e.index == 10 ** 20
+            }
+        "#;
+        assert_eq!(run(source).unwrap(), Value::Boolean(true));
+        let error = run("range(10 ** 20)").unwrap_err().to_string();
+        assert!(error.contains("does not fit"), "{}", error);
+    }
+
+    #[test]
+    fn test_integer_errors_are_catchable() {
+        let source = r#"
+            caught := []
+            try {
+                1 % 0
+            } catch DivisionByZero {
+                caught = caught + ["mod"]
+            }
+            try {
+                10 ** 10 ** 9
+            } catch OverflowError as e {
+                caught = caught + [e.message]
+            }
+            caught
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::String("mod".to_string()),
+                Value::String("Integer result is larger than 1048576 bits".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_strings_are_grapheme_aware() {
+        let strings = |items: &[&str]| {
+            Value::Array(items.iter().map(|s| Value::String(s.to_string())).collect())
+        };
+        let string = |s: &str| Value::String(s.to_string());
+        // "e" + combining acute accent, and a family emoji joined with ZWJs
+        let text = "\"cafe\u{301} \u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}!\"";
+        let cases = vec![
+            (format!("len({})", text), Value::Integer(7)),
+            (format!("{}[3]", text), string("e\u{301}")),
+            (
+                format!("{}[-2]", text),
+                string("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}"),
+            ),
+            (format!("{}[1..4]", text), string("afe\u{301}")),
+            (
+                format!("{}.reverse()", text),
+                string("!\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467} e\u{301}fac"),
+            ),
+            (format!("{}.find(\"!\")", text), Value::Integer(6)),
+            (format!("len({}.chars())", text), Value::Integer(12)),
+            (format!("len({}.bytes())", text), Value::Integer(26)),
+            (
+                "\"e\u{301}\".graphemes()".to_string(),
+                strings(&["e\u{301}"]),
+            ),
+            (
+                "\"é\".bytes()".to_string(),
+                Value::Array(vec![Value::Integer(195), Value::Integer(169)]),
+            ),
+            (
+                "[1, 2, 3, 4][1..=2]".to_string(),
+                Value::Array(vec![Value::Integer(2), Value::Integer(3)]),
+            ),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(run(&source).unwrap(), expected, "{}", source);
+        }
+
+        let source =
+            "letters := []\nfor g in \"ne\u{301}e\" {\n    letters = letters + [g]\n}\nletters";
+        assert_eq!(run(source).unwrap(), strings(&["n", "e\u{301}", "e"]));
+        assert!(run("\"abc\"[1..5]").is_err());
+    }
+
+    #[test]
+    fn test_padding_uses_display_width() {
+        let source = r#"
+            rows := [["名前", "値"], ["name", "v"], ["🎉", "x"]]
+            lines := []
+            for row in rows {
+                lines = lines + [row[0].pad_right(6) + "|" + row[1].pad_left(3, ".")]
+            }
+            [lines, "東京".width(), "ab".pad_left(5, "漢")]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::Array(vec![
+                    Value::String("名前  |.値".to_string()),
+                    Value::String("name  |..v".to_string()),
+                    Value::String("🎉    |..x".to_string()),
+                ]),
+                Value::Integer(4),
+                Value::String("漢ab".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_default_named_and_variadic_parameters() {
+        let source = r#"
+            fn deploy(env: string, dry_run: bool = false, ...targets: string) {
+                return "${env} ${dry_run} ${targets}"
+            }
+            fn window(start, size = 10, end = start + size) {
+                return [start, size, end]
+            }
+            hosts := ["a", "b"]
+            [deploy("prod"), deploy("prod", true, "web"), deploy("prod", false, ...hosts), deploy(dry_run: true, env: "dev"), window(5), window(5, end: 7), window(...[1, 2])]
+        "#;
+        let ints = |items: &[i64]| Value::Array(items.iter().map(|i| Value::Integer(*i)).collect());
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::String("prod false []".to_string()),
+                Value::String("prod true [web]".to_string()),
+                Value::String("prod false [a, b]".to_string()),
+                Value::String("dev true []".to_string()),
+                ints(&[5, 10, 15]),
+                ints(&[5, 10, 7]),
+                ints(&[1, 2, 3]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_call_argument_errors() {
+        let prelude = "fn deploy(env: string, dry_run: bool = false, ...targets: string) {\n}\nfn window(start, size = 10) {\n}\n";
+        let cases = [
+            (
+                "deploy()",
+                "Function deploy expects at least 1 argument, got 0",
+            ),
+            (
+                "window()",
+                "Function window expects 1 to 2 arguments, got 0",
+            ),
+            (
+                "window(1, 2, 3)",
+                "Function window expects 1 to 2 arguments, got 3",
+            ),
+            (
+                "window(size: 2)",
+                "Function window is missing argument 'start'",
+            ),
+            (
+                "deploy(\"prod\", colour: 1)",
+                "Function deploy has no parameter 'colour'",
+            ),
+            (
+                "deploy(\"prod\", env: \"dev\")",
+                "Function deploy got more than one value for 'env'",
+            ),
+            (
+                "deploy(\"prod\", targets: [])",
+                "Variadic parameter 'targets' of deploy cannot be passed by name",
+            ),
+            (
+                "deploy(\"prod\", true, 3)",
+                "argument 'targets' to deploy must be string, got int",
+            ),
+            (
+                "len(value: \"x\")",
+                "len does not take named arguments, got 'value'",
+            ),
+            (
+                "window(...5)",
+                "Cannot spread int into arguments, expected an array",
+            ),
+        ];
+        for (call, expected) in cases {
+            let error = run(&format!("{}{}", prelude, call)).unwrap_err();
+            assert_eq!(error.to_string(), expected, "{}", call);
+        }
+    }
+
+    #[test]
+    fn test_generators_are_lazy() {
+        let source = r#"
+            log := []
+            fn countdown(n) {
+                while n > 0 {
+                    yield n
+                    n -= 1
+                }
+            }
+            fn naturals() {
+                i := 0
+                while true {
+                    log = log + ["made ${i}"]
+                    yield i
+                    i += 1
+                }
+            }
+            seen := []
+            for n in countdown(3) {
+                seen = seen + [n]
+            }
+            for i in naturals() {
+                if i == 2 {
+                    break
+                }
+                log = log + ["got ${i}"]
+            }
+            unused := naturals()
+            g := countdown(2)
+            [seen, log, g.next(), g.collect(), g.next()]
+        "#;
+        let strings = |items: &[&str]| {
+            Value::Array(items.iter().map(|s| Value::String(s.to_string())).collect())
+        };
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::Array(vec![
+                    Value::Integer(3),
+                    Value::Integer(2),
+                    Value::Integer(1)
+                ]),
+                strings(&["made 0", "got 0", "made 1", "got 1", "made 2"]),
+                Value::builtin_variant("Some", vec![Value::Integer(2)]),
+                Value::Array(vec![Value::Integer(1)]),
+                Value::builtin_variant("None", vec![]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_yield_inside_expressions_makes_a_generator() {
+        let source = r#"
+            fn g() {
+                v is if true {
+                    yield 1
+                    2
+                } else {
+                    3
+                }
+            }
+            fn h() {
+                return {
+                    yield 3
+                    4
+                }
+            }
+            fn k() {
+                f := fn() { yield 0 }
+                5
+            }
+            [g().collect(), h().collect(), k()]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::Array(vec![Value::Integer(1)]),
+                Value::Array(vec![Value::Integer(3)]),
+                Value::Integer(5),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_iterator_protocol() {
+        let source = r#"
+            struct Pair {
+                first: int,
+                second: int
+            }
+            impl Pair {
+                fn iter(self) {
+                    yield self.first
+                    yield self.second
+                }
+            }
+            fn failing() {
+                yield 1
+                yield 1 / 0
+            }
+            total := 0
+            for x in Pair { first: 3, second: 4 } {
+                total += x
+            }
+            lines := []
+            for line in $(yes) {
+                if len(lines) == 3 {
+                    break
+                }
+                lines = lines + [line]
+            }
+            caught := "no"
+            try {
+                for x in failing() {
+                    total += x
+                }
+            } catch DivisionByZero {
+                caught = "yes"
+            }
+            [total, lines, caught]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::Integer(8),
+                Value::Array(vec![Value::String("y".to_string()); 3]),
+                Value::String("yes".to_string()),
+            ])
+        );
+        assert_eq!(
+            run("yield 1").unwrap_err().to_string(),
+            "'yield' outside of a generator function"
+        );
+        assert_eq!(
+            run("struct Empty { }\nfor x in Empty { } { }")
+                .unwrap_err()
+                .to_string(),
+            "Cannot iterate over Empty: it has no 'iter' method"
+        );
+    }
+
+    #[test]
+    fn test_read_lines_streams_a_file() {
+        let path = std::env::temp_dir().join(format!("shellux-lines-{}.txt", std::process::id()));
+        std::fs::write(&path, "one\ntwo\nthree\n").unwrap();
+        let source = format!(
+            r#"
+            lines := read_lines("{}")
+            first := lines.next()
+            rest := []
+            for line in lines {{
+                rest = rest + [line]
+            }}
+            [first, rest]
+        "#,
+            path.display()
+        );
+        let result = run(&source);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            result.unwrap(),
+            Value::Array(vec![
+                Value::builtin_variant("Some", vec![Value::String("one".to_string())]),
+                Value::Array(vec![
+                    Value::String("two".to_string()),
+                    Value::String("three".to_string()),
+                ]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_spawned_tasks_run_concurrently() {
+        let source = r#"
+            tasks := []
+            for i in 0..4 {
+                tasks = tasks + [spawn {
+                    $(sleep 0.5)
+                    i * 10
+                }]
+            }
+            await_all(tasks)
+        "#;
+        let started = std::time::Instant::now();
+        let result = run(source).unwrap();
+        assert!(started.elapsed() < std::time::Duration::from_millis(1500));
+        assert_eq!(
+            result,
+            Value::Array(vec![
+                Value::Integer(0),
+                Value::Integer(10),
+                Value::Integer(20),
+                Value::Integer(30),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_tasks_channels_and_errors() {
+        let source = r#"
+            ch := channel()
+            producer := spawn {
+                for i in 1..=3 {
+                    ch.send(i)
+                }
+                ch.close()
+                "done"
+            }
+            total := 0
+            for n in ch {
+                total += n
+            }
+            failing := spawn { 1 / 0 }
+            caught := []
+            for attempt in 0..2 {
+                try {
+                    await failing
+                } catch DivisionByZero as e {
+                    caught = caught + [e.message]
+                }
+            }
+            nested := spawn { await spawn { "inner" } }
+            [total, await producer, await nested, ch.recv(), caught]
+        "#;
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::Integer(6),
+                Value::String("done".to_string()),
+                Value::String("inner".to_string()),
+                Value::builtin_variant("None", vec![]),
+                Value::Array(vec![Value::String("Division by zero".to_string()); 2]),
+            ])
+        );
+
+        let cases = [
+            ("await 5", "Cannot await int, expected a task"),
+            (
+                "ch := channel()\nch.close()\nch.send(1)",
+                "Cannot send on a closed channel",
+            ),
+            ("await spawn { throw(\"boom\") }", "boom"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(run(source).unwrap_err().to_string(), expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_bindings_shadow_builtins() {
+        let source = r#"
+            fn try_operation(fail) {
+                if fail {
+                    return Err("disk full")
+                }
+                return Ok(42)
+            }
+            outcomes := []
+            for fail in [false, true] {
+                result := try_operation(fail)
+                match result {
+                    Ok(value) => outcomes = outcomes + ["Success: ${value}"],
+                    Err(error) => outcomes = outcomes + ["Failed: ${error}"]
+                }
+            }
+            try {
+                throw(error("NotFound", "no config"))
+            } catch NotFound as error {
+                outcomes = outcomes + [error.message]
+            }
+            range is 5
+            methods is "none"
+            fn channel(name) {
+                return "@" + name
+            }
+            [outcomes, range, methods, channel("ops")]
+        "#;
+        let string = |s: &str| Value::String(s.to_string());
+        assert_eq!(
+            run(source).unwrap(),
+            Value::Array(vec![
+                Value::Array(vec![
+                    string("Success: 42"),
+                    string("Failed: disk full"),
+                    string("no config"),
+                ]),
+                Value::Integer(5),
+                string("none"),
+                string("@ops"),
+            ])
+        );
+    }
 }
@@ -0,0 +1,130 @@
+use super::{Environment, Interpreter, Value};
+use crate::builtins::register_builtins;
+use crate::lexer::token::TokenType;
+use crate::lexer::Lexer;
+use crate::parser::ast::Program;
+use crate::parser::Parser;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Modules loaded so far, keyed by canonical path, and the chain of modules
+/// currently being loaded. Copies share what is loaded, so generators and
+/// tasks see the same module instances as the script that started them.
+#[derive(Clone, Default)]
+pub(super) struct Modules {
+    loaded: Arc<Mutex<HashMap<PathBuf, HashMap<String, Value>>>>,
+    loading: Vec<PathBuf>,
+}
+
+impl Modules {
+    fn loaded(&self) -> MutexGuard<'_, HashMap<PathBuf, HashMap<String, Value>>> {
+        self.loaded.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Interpreter {
+    /// Sets the file being run. Its imports resolve relative to it, and
+    /// importing it back from one of them is reported as a cycle.
+    pub fn set_script_path(&mut self, path: PathBuf) {
+        if let Ok(canonical) = path.canonicalize() {
+            self.modules.loading.push(canonical);
+        }
+        self.script_path = Some(path);
+    }
+
+    /// Loads the module at `path` and returns its exports. Each module runs
+    /// once, in its own global scope; later imports reuse the result.
+    pub(super) fn import_module(&mut self, path: &str) -> Result<HashMap<String, Value>> {
+        let resolved = self.resolve_module(path)?;
+        if let Some(exports) = self.modules.loaded().get(&resolved) {
+            return Ok(exports.clone());
+        }
+        if let Some(start) = self.modules.loading.iter().position(|p| *p == resolved) {
+            let cycle: Vec<String> = self.modules.loading[start..]
+                .iter()
+                .chain([&resolved])
+                .map(|p| p.display().to_string())
+                .collect();
+            return Err(anyhow!("Cyclic import: {}", cycle.join(" -> ")));
+        }
+
+        let source = fs::read_to_string(&resolved)
+            .map_err(|e| anyhow!("Cannot read module '{}': {}", path, e))?;
+        let program = parse_module(&source).map_err(|e| anyhow!("In module '{}': {}", path, e))?;
+
+        let globals = Environment::new();
+        register_builtins(&globals);
+        let outer_environment = mem::replace(&mut self.environment, globals.clone());
+        let outer_path = self.script_path.replace(resolved.clone());
+        self.modules.loading.push(resolved.clone());
+        // A module's top-level `return` ends the module, not the importer
+        let outer_exit_code = self.exit_code.take();
+
+        let result = self.interpret(program);
+
+        self.exit_code = outer_exit_code;
+        self.modules.loading.pop();
+        self.script_path = outer_path;
+        self.environment = outer_environment;
+        result?;
+
+        // Should another thread have loaded the module meanwhile, keep the
+        // instance it stored so that every importer shares one
+        let exports = globals.exports();
+        Ok(self
+            .modules
+            .loaded()
+            .entry(resolved)
+            .or_insert(exports)
+            .clone())
+    }
+
+    /// Finds `path` relative to the importing script's directory (or the
+    /// working directory), then in each directory listed in `SHELLUX_PATH`.
+    fn resolve_module(&self, path: &str) -> Result<PathBuf> {
+        let base = match self.script_path.as_deref().and_then(Path::parent) {
+            Some(dir) => dir.to_path_buf(),
+            None => env::current_dir()?,
+        };
+        let mut candidates = vec![base.join(path)];
+        if let Some(search_path) = env::var_os("SHELLUX_PATH") {
+            candidates.extend(env::split_paths(&search_path).map(|dir| dir.join(path)));
+        }
+
+        candidates
+            .into_iter()
+            .find(|candidate| candidate.is_file())
+            .map(|found| found.canonicalize())
+            .transpose()?
+            .ok_or_else(|| anyhow!("Module not found: '{}'", path))
+    }
+}
+
+/// The name a whole-module import binds without `as`: the file's stem.
+pub(super) fn default_name(path: &str) -> Result<String> {
+    Path::new(path)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("Cannot name module '{}'; use `as`", path))
+}
+
+fn parse_module(source: &str) -> Result<Program> {
+    let tokens = Lexer::new(source).tokenize();
+    for token in &tokens {
+        if let TokenType::Error(msg) = &token.token_type {
+            return Err(anyhow!(
+                "Lexer error at line {}, column {}: {}",
+                token.line,
+                token.column,
+                msg
+            ));
+        }
+    }
+    Parser::new(tokens).parse()
+}
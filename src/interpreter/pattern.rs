@@ -0,0 +1,165 @@
+use super::{Interpreter, Value};
+use crate::parser::ast::{BinaryOperator, Pattern};
+use anyhow::Result;
+
+impl Interpreter {
+    /// Tests `value` against `pattern`, appending any names it binds to
+    /// `bindings`. On a failed match the bindings added so far are discarded.
+    pub(super) fn match_pattern(
+        &mut self,
+        pattern: &Pattern,
+        value: &Value,
+        bindings: &mut Vec<(String, Value)>,
+    ) -> Result<bool> {
+        let mark = bindings.len();
+        let matched = self.match_pattern_inner(pattern, value, bindings)?;
+        if !matched {
+            bindings.truncate(mark);
+        }
+        Ok(matched)
+    }
+
+    fn match_pattern_inner(
+        &mut self,
+        pattern: &Pattern,
+        value: &Value,
+        bindings: &mut Vec<(String, Value)>,
+    ) -> Result<bool> {
+        match pattern {
+            Pattern::Wildcard => Ok(true),
+
+            Pattern::Identifier(name) => {
+                bindings.push((name.clone(), value.clone()));
+                Ok(true)
+            }
+
+            Pattern::Literal(expr) => {
+                let literal = self.evaluate_expression(expr.clone())?;
+                Ok(self
+                    .apply_binary_operator(BinaryOperator::Equal, value.clone(), literal)?
+                    .is_truthy())
+            }
+
+            Pattern::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                if !matches!(
+                    value,
+                    Value::Integer(_) | Value::BigInt(_) | Value::Float(_)
+                ) {
+                    return Ok(false);
+                }
+                let start = self.evaluate_expression(start.clone())?;
+                let end = self.evaluate_expression(end.clone())?;
+                let upper = if *inclusive {
+                    BinaryOperator::LessEqual
+                } else {
+                    BinaryOperator::Less
+                };
+                Ok(self
+                    .apply_binary_operator(BinaryOperator::GreaterEqual, value.clone(), start)?
+                    .is_truthy()
+                    && self
+                        .apply_binary_operator(upper, value.clone(), end)?
+                        .is_truthy())
+            }
+
+            Pattern::Or(alternatives) => {
+                for alternative in alternatives {
+                    if self.match_pattern(alternative, value, bindings)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+
+            Pattern::Array(elements) => match value {
+                Value::Array(items) => self.match_elements(elements, items, bindings),
+                _ => Ok(false),
+            },
+
+            // Bare `a, b` patterns unpack tuples and arrays alike
+            Pattern::Tuple(elements) => match value {
+                Value::Tuple(items) | Value::Array(items) => {
+                    self.match_elements(elements, items, bindings)
+                }
+                _ => Ok(false),
+            },
+
+            Pattern::Map(entries) => {
+                let map = match value {
+                    Value::Map(map) => map,
+                    _ => return Ok(false),
+                };
+                for (key, sub_pattern) in entries {
+                    match map.get(key) {
+                        Some(item) if self.match_pattern(sub_pattern, item, bindings)? => {}
+                        _ => return Ok(false),
+                    }
+                }
+                Ok(true)
+            }
+
+            Pattern::Variant {
+                enum_name,
+                name,
+                fields,
+            } => match value {
+                Value::Variant {
+                    enum_name: actual_enum,
+                    name: actual,
+                    values,
+                } if actual == name && enum_name.as_ref().is_none_or(|e| e == actual_enum) => {
+                    self.match_elements(fields, values, bindings)
+                }
+                _ => Ok(false),
+            },
+
+            // The parser only produces `..` inside array and tuple patterns
+            Pattern::Rest(_) => Ok(false),
+        }
+    }
+
+    /// Matches array or tuple element patterns, where at most one `..rest` element
+    /// absorbs whatever the patterns before and after it leave over.
+    fn match_elements(
+        &mut self,
+        elements: &[Pattern],
+        items: &[Value],
+        bindings: &mut Vec<(String, Value)>,
+    ) -> Result<bool> {
+        let rest_index = elements.iter().position(|p| matches!(p, Pattern::Rest(_)));
+
+        let (before, after) = match rest_index {
+            Some(index) => (&elements[..index], &elements[index + 1..]),
+            None => (elements, &elements[elements.len()..]),
+        };
+
+        let fixed = before.len() + after.len();
+        if items.len() < fixed || (rest_index.is_none() && items.len() != fixed) {
+            return Ok(false);
+        }
+
+        for (sub_pattern, item) in before.iter().zip(items) {
+            if !self.match_pattern(sub_pattern, item, bindings)? {
+                return Ok(false);
+            }
+        }
+
+        let tail = &items[items.len() - after.len()..];
+        for (sub_pattern, item) in after.iter().zip(tail) {
+            if !self.match_pattern(sub_pattern, item, bindings)? {
+                return Ok(false);
+            }
+        }
+
+        if let Some(Pattern::Rest(Some(name))) = rest_index.map(|index| &elements[index]) {
+            let middle = items[before.len()..items.len() - after.len()].to_vec();
+            bindings.push((name.clone(), Value::Array(middle)));
+        }
+
+        Ok(true)
+    }
+}
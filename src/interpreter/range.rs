@@ -0,0 +1,85 @@
+use anyhow::{anyhow, Result};
+use std::fmt;
+
+/// A lazy integer range produced by `a..b`, `a..=b`, `a..b step n` or
+/// `range(a, b)`. Elements are computed on demand, never materialized.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Range {
+    pub start: i64,
+    pub end: i64,
+    pub step: i64,
+    pub inclusive: bool,
+}
+
+impl Range {
+    pub fn new(start: i64, end: i64, step: i64, inclusive: bool) -> Result<Self> {
+        if step == 0 {
+            return Err(anyhow!("Range step cannot be zero"));
+        }
+        Ok(Self {
+            start,
+            end,
+            step,
+            inclusive,
+        })
+    }
+
+    /// The number of values, exactly: `i64::MIN..=i64::MAX` has 2^64 of them.
+    pub fn len(&self) -> i128 {
+        let (start, end, step) = (self.start as i128, self.end as i128, self.step as i128);
+        // Distance to the first value past the end, in the direction of travel
+        let span = if step > 0 {
+            end - start + i128::from(self.inclusive)
+        } else {
+            start - end + i128::from(self.inclusive)
+        };
+        if span <= 0 {
+            0
+        } else {
+            (span + step.abs() - 1) / step.abs()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the element at `index`, counting from the end when negative.
+    pub fn get(&self, index: i64) -> Option<i64> {
+        let index = if index < 0 {
+            self.len() + index as i128
+        } else {
+            index as i128
+        };
+        self.value_at(index)
+    }
+
+    pub fn contains(&self, value: i64) -> bool {
+        let offset = value as i128 - self.start as i128;
+        let step = self.step as i128;
+        offset % step == 0 && (0..self.len()).contains(&(offset / step))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = i64> {
+        let range = *self;
+        (0..range.len()).map_while(move |i| range.value_at(i))
+    }
+
+    fn value_at(&self, index: i128) -> Option<i64> {
+        if index < 0 || index >= self.len() {
+            return None;
+        }
+        i64::try_from(self.start as i128 + index * self.step as i128).ok()
+    }
+}
+
+impl fmt::Display for Range {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = if self.inclusive { "..=" } else { ".." };
+        write!(f, "{}{}{}", self.start, operator, self.end)?;
+        if self.step != 1 {
+            write!(f, " step {}", self.step)?;
+        }
+        Ok(())
+    }
+}
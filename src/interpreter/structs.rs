@@ -0,0 +1,110 @@
+use super::{Environment, Interpreter, Value};
+use crate::parser::ast::{Expr, Stmt, Type};
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+
+/// A `struct` declaration. Its methods live in a scope of their own, shared
+/// by every instance, so an `impl` block also reaches values built before it.
+#[derive(Debug)]
+pub struct StructDef {
+    pub name: String,
+    pub fields: Vec<(String, Type)>,
+    pub methods: Environment,
+}
+
+impl StructDef {
+    pub fn field_index(&self, field: &str) -> Option<usize> {
+        self.fields.iter().position(|(name, _)| name == field)
+    }
+}
+
+/// Looks up a method declared in an `impl` block for an instance or a struct.
+pub(super) fn struct_method(receiver: &Value, method: &str) -> Option<Value> {
+    match receiver {
+        Value::Struct { definition, .. } | Value::StructType(definition) => {
+            definition.methods.get(method)
+        }
+        _ => None,
+    }
+}
+
+impl Interpreter {
+    pub(super) fn define_struct(&mut self, name: String, fields: Vec<(String, Type)>) -> Value {
+        let definition = Value::StructType(Arc::new(StructDef {
+            name: name.clone(),
+            fields,
+            methods: Environment::new(),
+        }));
+        self.environment.define(name, definition.clone());
+        definition
+    }
+
+    pub(super) fn define_methods(&mut self, name: &str, methods: Vec<Stmt>) -> Result<Value> {
+        let definition = self.struct_definition(name)?;
+        for method in methods {
+            if let Stmt::Function {
+                name,
+                parameters,
+                return_type,
+                body,
+                ..
+            } = method
+            {
+                let function = Value::Function {
+                    name: format!("{}.{}", definition.name, name),
+                    parameters,
+                    return_type,
+                    body,
+                    closure: self.environment.clone(),
+                };
+                definition.methods.define(name, function);
+            }
+        }
+        Ok(Value::Nil)
+    }
+
+    /// Builds `Name { field: value, ... }`. Every declared field must be
+    /// given exactly once, and values are checked against the field types.
+    pub(super) fn construct_struct(
+        &mut self,
+        name: &str,
+        fields: Vec<(String, Expr)>,
+    ) -> Result<Value> {
+        let definition = self.struct_definition(name)?;
+        let mut values: Vec<Option<Value>> = vec![None; definition.fields.len()];
+        for (field, expr) in fields {
+            let index = definition
+                .field_index(&field)
+                .ok_or_else(|| anyhow!("{} has no field '{}'", name, field))?;
+            if values[index].is_some() {
+                return Err(anyhow!("Field '{}' of {} given twice", field, name));
+            }
+            let value = self.evaluate_expression(expr)?;
+            let value = self.enforce_type(value, Some(&definition.fields[index].1), || {
+                format!("field '{}' of {}", field, name)
+            })?;
+            values[index] = Some(value);
+        }
+
+        let fields = values
+            .into_iter()
+            .zip(&definition.fields)
+            .map(|(value, (field, _))| {
+                value.ok_or_else(|| anyhow!("Missing field '{}' in {}", field, name))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Value::Struct { definition, fields })
+    }
+
+    fn struct_definition(&self, name: &str) -> Result<Arc<StructDef>> {
+        match self.environment.get(name) {
+            Some(Value::StructType(definition)) => Ok(definition),
+            Some(other) => Err(anyhow!(
+                "'{}' is a {}, not a struct",
+                name,
+                other.type_name()
+            )),
+            None => Err(anyhow!("Undefined struct: {}", name)),
+        }
+    }
+}
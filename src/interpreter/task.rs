@@ -0,0 +1,166 @@
+use super::{error_to_value, ControlFlow, Environment, Interpreter, ScriptError, Value};
+use crate::parser::ast::Stmt;
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError};
+use tokio::runtime::{Builder, Runtime};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+
+/// The runtime tasks run on. Scripts and the commands they start block, so
+/// every task gets a thread from the blocking pool rather than a worker.
+fn runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("shellux-task")
+            .build()
+            .expect("failed to start the task runtime")
+    })
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A block started by `spawn { ... }`, running alongside the script.
+/// Copies refer to the same task.
+#[derive(Clone)]
+pub struct Task(Arc<Mutex<TaskState>>);
+
+enum TaskState {
+    Running(JoinHandle<Result<Value>>),
+    // Kept so that awaiting again gives the same value or raises the same error
+    Finished(std::result::Result<Value, ScriptError>),
+}
+
+impl Task {
+    /// Waits for the task to finish and returns the value of its block, or
+    /// raises the error that stopped it.
+    pub fn join(&self) -> Result<Value> {
+        let mut state = lock(&self.0);
+        if let TaskState::Running(handle) = &mut *state {
+            let result = match runtime().block_on(handle) {
+                Ok(result) => result.map_err(|e| ScriptError::from_value(error_to_value(e))),
+                Err(e) => Err(ScriptError::new(
+                    "RuntimeError",
+                    format!("Task failed: {}", e),
+                )),
+            };
+            *state = TaskState::Finished(result);
+        }
+        match &*state {
+            TaskState::Finished(result) => result.clone().map_err(Into::into),
+            TaskState::Running(_) => unreachable!("the task was just awaited"),
+        }
+    }
+}
+
+impl fmt::Debug for Task {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<task>")
+    }
+}
+
+impl PartialEq for Task {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// An unbounded queue for passing values between tasks. Copies share the
+/// queue, so any task holding one can send, receive or close it.
+#[derive(Clone)]
+pub struct Channel {
+    sender: Arc<Mutex<Option<UnboundedSender<Value>>>>,
+    receiver: Arc<Mutex<UnboundedReceiver<Value>>>,
+}
+
+impl Channel {
+    pub fn new() -> Self {
+        let (sender, receiver) = unbounded_channel();
+        Self {
+            sender: Arc::new(Mutex::new(Some(sender))),
+            receiver: Arc::new(Mutex::new(receiver)),
+        }
+    }
+
+    pub fn send(&self, value: Value) -> Result<()> {
+        lock(&self.sender)
+            .as_ref()
+            .and_then(|sender| sender.send(value).ok())
+            .ok_or_else(|| anyhow!("Cannot send on a closed channel"))
+    }
+
+    /// Waits for the next value. Returns `None` once the channel is closed
+    /// and everything sent before that has been received.
+    pub fn recv(&self) -> Option<Value> {
+        lock(&self.receiver).blocking_recv()
+    }
+
+    pub fn close(&self) {
+        lock(&self.sender).take();
+    }
+}
+
+impl Default for Channel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<channel>")
+    }
+}
+
+impl PartialEq for Channel {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.receiver, &other.receiver)
+    }
+}
+
+/// Waits for `value`, which must be a task.
+pub fn await_task(value: &Value) -> Result<Value> {
+    match value {
+        Value::Task(task) => task.join(),
+        other => Err(anyhow!(
+            "Cannot await {}, expected a task",
+            other.type_name()
+        )),
+    }
+}
+
+impl Interpreter {
+    /// Starts `body` on the runtime's blocking pool and returns its task.
+    ///
+    /// The block runs in a new scope of the current one, so it sees the
+    /// variables around it. Errors are held until the task is awaited, and a
+    /// task nobody awaits is abandoned when the script ends.
+    pub(super) fn spawn(&self, body: Vec<Stmt>) -> Value {
+        let environment = Environment::new_with_parent(self.environment.clone());
+        let runtime_types = self.runtime_types;
+        let script_path = self.script_path.clone();
+        let modules = self.modules.clone();
+        let handle = runtime().spawn_blocking(move || {
+            let mut interpreter = Interpreter {
+                environment,
+                runtime_types,
+                script_path,
+                modules,
+                yielder: None,
+                exit_code: None,
+            };
+            match interpreter.execute_block(body) {
+                // A task is not a function, so `?` has nothing to return from
+                Err(e) if matches!(e.downcast_ref(), Some(ControlFlow::Propagate(_))) => {
+                    Err(anyhow!("{}", e))
+                }
+                result => Interpreter::function_result(result),
+            }
+        });
+        Value::Task(Task(Arc::new(Mutex::new(TaskState::Running(handle)))))
+    }
+}
@@ -0,0 +1,98 @@
+use super::{Interpreter, ScriptError, Value};
+use crate::parser::ast::Type;
+use anyhow::Result;
+
+impl Interpreter {
+    /// Checks a value bound to an annotated name, parameter or return, and
+    /// widens integers where floats are declared. `describe` names the
+    /// binding in the error. With runtime types disabled the value is still
+    /// widened, so that the flag only skips the check and never changes a
+    /// result.
+    pub(super) fn enforce_type(
+        &self,
+        value: Value,
+        annotation: Option<&Type>,
+        describe: impl FnOnce() -> String,
+    ) -> Result<Value> {
+        let Some(ty) = annotation else {
+            return Ok(value);
+        };
+        if !self.runtime_types || matches_type(&value, ty) {
+            Ok(widen(value, ty))
+        } else {
+            Err(ScriptError::new(
+                "TypeError",
+                format!("{} must be {}, got {}", describe(), ty, value.type_name()),
+            )
+            .into())
+        }
+    }
+}
+
+/// Whether `value` conforms to `ty`, looking inside arrays and maps.
+fn matches_type(value: &Value, ty: &Type) -> bool {
+    match (ty, value) {
+        (Type::Any, _) => true,
+        (Type::Int, Value::Integer(_) | Value::BigInt(_)) => true,
+        (Type::Float, Value::Float(_) | Value::Integer(_) | Value::BigInt(_)) => true,
+        (Type::String, Value::String(_)) => true,
+        (Type::Bool, Value::Boolean(_)) => true,
+        (Type::Array(element), Value::Array(items)) => {
+            items.iter().all(|item| matches_type(item, element))
+        }
+        (Type::Tuple(types), Value::Tuple(items)) => {
+            types.len() == items.len() && items.iter().zip(types).all(|(v, t)| matches_type(v, t))
+        }
+        (
+            Type::Map {
+                key_type,
+                value_type,
+            },
+            Value::Map(map),
+        ) => {
+            // Map keys are always strings
+            matches!(**key_type, Type::String | Type::Any)
+                && map.values().all(|item| matches_type(item, value_type))
+        }
+        (
+            Type::Function { parameters, .. },
+            Value::Function {
+                parameters: actual,
+                body,
+                ..
+            },
+        ) => {
+            // Built-ins have no declared parameters to compare
+            body.is_empty() || parameters.len() == actual.len()
+        }
+        // `nil` is the missing error in `return x, nil`; structs and enums
+        // have no such empty value
+        (Type::Custom(name), Value::Nil) => name == "error",
+        (Type::Custom(name), value) => value.type_name() == name,
+        _ => false,
+    }
+}
+
+/// Converts integers to floats wherever `ty` declares a float.
+fn widen(value: Value, ty: &Type) -> Value {
+    match (ty, value) {
+        (Type::Float, Value::Integer(i)) => Value::Float(i as f64),
+        (Type::Float, Value::BigInt(i)) => Value::Float(super::integer::to_f64(&i)),
+        (Type::Array(element), Value::Array(items)) => {
+            Value::Array(items.into_iter().map(|item| widen(item, element)).collect())
+        }
+        (Type::Tuple(types), Value::Tuple(items)) => Value::Tuple(
+            items
+                .into_iter()
+                .zip(types)
+                .map(|(item, ty)| widen(item, ty))
+                .collect(),
+        ),
+        (Type::Map { value_type, .. }, Value::Map(map)) => Value::Map(
+            map.into_iter()
+                .map(|(key, item)| (key, widen(item, value_type)))
+                .collect(),
+        ),
+        (_, value) => value,
+    }
+}
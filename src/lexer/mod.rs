@@ -1,9 +1,10 @@
 pub mod token;
 
-pub use token::{keyword_or_identifier, Token, TokenType};
+pub use token::{keyword_or_identifier, StringSegment, Token, TokenType};
+
+use num_bigint::BigInt;
 
 pub struct Lexer<'a> {
-    input: &'a str,
     chars: std::str::Chars<'a>,
     current_char: Option<char>,
     line: usize,
@@ -17,7 +18,6 @@ impl<'a> Lexer<'a> {
         let current_char = chars.next();
 
         Self {
-            input,
             chars,
             current_char,
             line: 1,
@@ -31,7 +31,7 @@ impl<'a> Lexer<'a> {
 
         loop {
             let token = self.next_token();
-            let is_eof = matches!(token.token_type, TokenType::EOF);
+            let is_eof = matches!(token.token_type, TokenType::Eof);
             tokens.push(token);
             if is_eof {
                 break;
@@ -86,11 +86,12 @@ impl<'a> Lexer<'a> {
             Some(c) if c.is_ascii_alphabetic() || c == '_' => self.read_identifier(),
 
             // Strings
+            Some('"') if self.peek() == Some('"') && self.peek_ahead(1) == Some('"') => {
+                self.read_triple_quote_string()
+            }
             Some('"') => self.read_string(),
             Some('\'') => self.read_single_quote_string(),
 
-            // (Triple-quoted strings handled in read_string method)
-
             // Command execution $( ... )
             Some('$') if self.peek() == Some('(') => self.read_command(),
 
@@ -129,31 +130,51 @@ impl<'a> Lexer<'a> {
 
             Some('*') => {
                 self.advance();
-                if self.current_char == Some('*') {
-                    self.advance();
-                    Token::new(TokenType::Power, start_line, start_column, 2)
-                } else {
-                    Token::new(TokenType::Multiply, start_line, start_column, 1)
+                match self.current_char {
+                    Some('*') => {
+                        self.advance();
+                        Token::new(TokenType::Power, start_line, start_column, 2)
+                    }
+                    Some('=') => {
+                        self.advance();
+                        Token::new(TokenType::MultiplyAssign, start_line, start_column, 2)
+                    }
+                    _ => Token::new(TokenType::Multiply, start_line, start_column, 1),
                 }
             }
 
             Some('/') => {
                 self.advance();
-                Token::new(TokenType::Divide, start_line, start_column, 1)
+                if self.current_char == Some('=') {
+                    self.advance();
+                    Token::new(TokenType::DivideAssign, start_line, start_column, 2)
+                } else {
+                    Token::new(TokenType::Divide, start_line, start_column, 1)
+                }
             }
 
             Some('%') => {
                 self.advance();
-                Token::new(TokenType::Modulo, start_line, start_column, 1)
+                if self.current_char == Some('=') {
+                    self.advance();
+                    Token::new(TokenType::ModuloAssign, start_line, start_column, 2)
+                } else {
+                    Token::new(TokenType::Modulo, start_line, start_column, 1)
+                }
             }
 
             Some('=') => {
                 self.advance();
-                if self.current_char == Some('=') {
-                    self.advance();
-                    Token::new(TokenType::Equal, start_line, start_column, 2)
-                } else {
-                    Token::new(TokenType::Assign, start_line, start_column, 1)
+                match self.current_char {
+                    Some('=') => {
+                        self.advance();
+                        Token::new(TokenType::Equal, start_line, start_column, 2)
+                    }
+                    Some('>') => {
+                        self.advance();
+                        Token::new(TokenType::FatArrow, start_line, start_column, 2)
+                    }
+                    _ => Token::new(TokenType::Assign, start_line, start_column, 1),
                 }
             }
 
@@ -282,9 +303,37 @@ impl<'a> Lexer<'a> {
                 Token::new(TokenType::Semicolon, start_line, start_column, 1)
             }
 
+            Some('?') => {
+                self.advance();
+                match self.current_char {
+                    Some('?') => {
+                        self.advance();
+                        Token::new(TokenType::QuestionQuestion, start_line, start_column, 2)
+                    }
+                    Some('.') => {
+                        self.advance();
+                        Token::new(TokenType::QuestionDot, start_line, start_column, 2)
+                    }
+                    _ => Token::new(TokenType::Question, start_line, start_column, 1),
+                }
+            }
+
             Some('.') => {
                 self.advance();
-                Token::new(TokenType::Dot, start_line, start_column, 1)
+                if self.current_char == Some('.') {
+                    self.advance();
+                    if self.current_char == Some('=') {
+                        self.advance();
+                        Token::new(TokenType::DotDotEqual, start_line, start_column, 3)
+                    } else if self.current_char == Some('.') {
+                        self.advance();
+                        Token::new(TokenType::Ellipsis, start_line, start_column, 3)
+                    } else {
+                        Token::new(TokenType::DotDot, start_line, start_column, 2)
+                    }
+                } else {
+                    Token::new(TokenType::Dot, start_line, start_column, 1)
+                }
             }
 
             Some(c) => {
@@ -375,7 +424,7 @@ impl<'a> Lexer<'a> {
             if c.is_ascii_digit() {
                 number_str.push(c);
                 self.advance();
-            } else if c == '.' && !is_float && self.peek().map_or(false, |ch| ch.is_ascii_digit()) {
+            } else if c == '.' && !is_float && self.peek().is_some_and(|ch| ch.is_ascii_digit()) {
                 is_float = true;
                 number_str.push(c);
                 self.advance();
@@ -396,11 +445,14 @@ impl<'a> Lexer<'a> {
                 ),
             }
         } else {
-            match number_str.parse::<i64>() {
-                Ok(value) => {
-                    Token::new(TokenType::Integer(value), start_line, start_column, length)
-                }
-                Err(_) => Token::error(
+            // Literals too large for i64 become big integers, as arithmetic does
+            let token_type = match number_str.parse::<i64>() {
+                Ok(value) => Some(TokenType::Integer(value)),
+                Err(_) => number_str.parse::<BigInt>().ok().map(TokenType::BigInt),
+            };
+            match token_type {
+                Some(token_type) => Token::new(token_type, start_line, start_column, length),
+                None => Token::error(
                     format!("Invalid integer: {}", number_str),
                     start_line,
                     start_column,
@@ -432,6 +484,8 @@ impl<'a> Lexer<'a> {
     fn read_string(&mut self) -> Token {
         let start_line = self.line;
         let start_column = self.column;
+        let start_position = self.position;
+        let mut segments = Vec::new();
         let mut string_value = String::new();
 
         // Skip opening quote
@@ -440,12 +494,12 @@ impl<'a> Lexer<'a> {
         while let Some(c) = self.current_char {
             if c == '"' {
                 self.advance(); // consume closing quote
-                let length = string_value.len() + 2; // +2 for quotes
-                return Token::new(
-                    TokenType::String(string_value),
+                return self.string_token(
+                    segments,
+                    string_value,
                     start_line,
                     start_column,
-                    length,
+                    start_position,
                 );
             } else if c == '\\' {
                 self.advance();
@@ -457,6 +511,7 @@ impl<'a> Lexer<'a> {
                         '\\' => string_value.push('\\'),
                         '"' => string_value.push('"'),
                         '\'' => string_value.push('\''),
+                        '$' => string_value.push('$'),
                         _ => {
                             string_value.push('\\');
                             string_value.push(escaped);
@@ -464,6 +519,10 @@ impl<'a> Lexer<'a> {
                     }
                     self.advance();
                 }
+            } else if c == '$' && self.peek() == Some('{') {
+                if let Err(error) = self.read_interpolation(&mut segments, &mut string_value) {
+                    return error;
+                }
             } else {
                 string_value.push(c);
                 self.advance();
@@ -473,6 +532,97 @@ impl<'a> Lexer<'a> {
         Token::error("Unterminated string".to_string(), start_line, start_column)
     }
 
+    /// Reads a `${...}` segment of a double- or triple-quoted string, flushing
+    /// the text read so far. The embedded source is kept raw for the parser;
+    /// braces and quotes inside it are tracked so `${m["}"]}` stays intact.
+    fn read_interpolation(
+        &mut self,
+        segments: &mut Vec<StringSegment>,
+        text: &mut String,
+    ) -> Result<(), Token> {
+        let line = self.line;
+        let column = self.column;
+
+        if !text.is_empty() {
+            segments.push(StringSegment::Text(std::mem::take(text)));
+        }
+
+        // Skip '${'
+        self.advance();
+        self.advance();
+
+        let mut source = String::new();
+        let mut depth = 1;
+        let mut quote: Option<char> = None;
+
+        while let Some(c) = self.current_char {
+            match quote {
+                Some(_) if c == '\\' => {
+                    source.push(c);
+                    self.advance();
+                    if let Some(escaped) = self.current_char {
+                        source.push(escaped);
+                        self.advance();
+                    }
+                    continue;
+                }
+                Some(q) if c == q => quote = None,
+                Some(_) => {}
+                None => match c {
+                    '"' | '\'' => quote = Some(c),
+                    '{' => depth += 1,
+                    '}' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            self.advance(); // consume closing brace
+                            segments.push(StringSegment::Code {
+                                source,
+                                line,
+                                column,
+                            });
+                            return Ok(());
+                        }
+                    }
+                    _ => {}
+                },
+            }
+            source.push(c);
+            self.advance();
+        }
+
+        Err(Token::error(
+            "Unterminated string interpolation".to_string(),
+            line,
+            column,
+        ))
+    }
+
+    /// Builds a plain `String` token, or an `InterpolatedString` when the
+    /// literal contained any `${...}` segments.
+    fn string_token(
+        &self,
+        mut segments: Vec<StringSegment>,
+        text: String,
+        line: usize,
+        column: usize,
+        start_position: usize,
+    ) -> Token {
+        let length = self.position - start_position;
+        if segments.is_empty() {
+            return Token::new(TokenType::String(text), line, column, length);
+        }
+
+        if !text.is_empty() {
+            segments.push(StringSegment::Text(text));
+        }
+        Token::new(
+            TokenType::InterpolatedString(segments),
+            line,
+            column,
+            length,
+        )
+    }
+
     fn read_single_quote_string(&mut self) -> Token {
         let start_line = self.line;
         let start_column = self.column;
@@ -503,6 +653,8 @@ impl<'a> Lexer<'a> {
     fn read_triple_quote_string(&mut self) -> Token {
         let start_line = self.line;
         let start_column = self.column;
+        let start_position = self.position;
+        let mut segments = Vec::new();
         let mut string_value = String::new();
 
         // Skip opening triple quotes
@@ -511,17 +663,21 @@ impl<'a> Lexer<'a> {
         self.advance();
 
         while let Some(c) = self.current_char {
-            if c == '"' && self.peek() == Some('"') && self.peek_ahead(2) == Some('"') {
+            if c == '"' && self.peek() == Some('"') && self.peek_ahead(1) == Some('"') {
                 self.advance(); // consume first "
                 self.advance(); // consume second "
                 self.advance(); // consume third "
-                let length = string_value.len() + 6; // +6 for triple quotes
-                return Token::new(
-                    TokenType::String(string_value),
+                return self.string_token(
+                    segments,
+                    string_value,
                     start_line,
                     start_column,
-                    length,
+                    start_position,
                 );
+            } else if c == '$' && self.peek() == Some('{') {
+                if let Err(error) = self.read_interpolation(&mut segments, &mut string_value) {
+                    return error;
+                }
             } else {
                 string_value.push(c);
                 self.advance();
@@ -539,12 +695,11 @@ impl<'a> Lexer<'a> {
         let start_line = self.line;
         let start_column = self.column;
         let mut command = String::new();
-        let mut paren_count = 0;
 
         // Skip '$('
         self.advance();
         self.advance();
-        paren_count = 1;
+        let mut paren_count = 1;
 
         while let Some(c) = self.current_char {
             if c == '(' {
@@ -591,7 +746,7 @@ mod tests {
         assert!(matches!(tokens[1].token_type, TokenType::Identifier(_)));
         assert!(matches!(tokens[2].token_type, TokenType::Is));
         assert!(matches!(tokens[3].token_type, TokenType::Integer(42)));
-        assert!(matches!(tokens[4].token_type, TokenType::EOF));
+        assert!(matches!(tokens[4].token_type, TokenType::Eof));
     }
 
     #[test]
@@ -619,4 +774,94 @@ mod tests {
             panic!("Expected command token");
         }
     }
+
+    #[test]
+    fn test_interpolated_string() {
+        let mut lexer = Lexer::new(r#""Hello, ${user["name"]}! \${literal}""#);
+        let tokens = lexer.tokenize();
+
+        assert_eq!(tokens.len(), 2); // interpolated string, EOF
+        if let TokenType::InterpolatedString(segments) = &tokens[0].token_type {
+            assert_eq!(
+                segments,
+                &vec![
+                    StringSegment::Text("Hello, ".to_string()),
+                    StringSegment::Code {
+                        source: r#"user["name"]"#.to_string(),
+                        line: 1,
+                        column: 9,
+                    },
+                    StringSegment::Text("! ${literal}".to_string()),
+                ]
+            );
+        } else {
+            panic!("Expected interpolated string token");
+        }
+    }
+
+    #[test]
+    fn test_triple_quoted_and_single_quoted_strings() {
+        let mut lexer = Lexer::new("\"\"\"a\n\"b\" c\"\"\" 'raw ${x}'");
+        let tokens = lexer.tokenize();
+
+        assert_eq!(tokens.len(), 3); // string, string, EOF
+        assert_eq!(
+            tokens[0].token_type,
+            TokenType::String("a\n\"b\" c".to_string())
+        );
+        assert_eq!(
+            tokens[1].token_type,
+            TokenType::String("raw ${x}".to_string())
+        );
+    }
+
+    #[test]
+    fn test_compound_assignment_operators() {
+        let mut lexer = Lexer::new("*= /= %= ** *");
+        let types: Vec<_> = lexer.tokenize().into_iter().map(|t| t.token_type).collect();
+
+        assert_eq!(
+            types,
+            vec![
+                TokenType::MultiplyAssign,
+                TokenType::DivideAssign,
+                TokenType::ModuloAssign,
+                TokenType::Power,
+                TokenType::Multiply,
+                TokenType::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_question_mark_operators() {
+        let mut lexer = Lexer::new("a ?? b?.c d?");
+        let types: Vec<_> = lexer.tokenize().into_iter().map(|t| t.token_type).collect();
+
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Identifier("a".to_string()),
+                TokenType::QuestionQuestion,
+                TokenType::Identifier("b".to_string()),
+                TokenType::QuestionDot,
+                TokenType::Identifier("c".to_string()),
+                TokenType::Identifier("d".to_string()),
+                TokenType::Question,
+                TokenType::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_integer_beyond_i64_is_big() {
+        let mut lexer = Lexer::new("9223372036854775807 9223372036854775808");
+        let tokens = lexer.tokenize();
+
+        assert_eq!(tokens[0].token_type, TokenType::Integer(i64::MAX));
+        assert_eq!(
+            tokens[1].token_type,
+            TokenType::BigInt(BigInt::from(i64::MAX) + 1)
+        );
+    }
 }
@@ -1,9 +1,14 @@
+use num_bigint::BigInt;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenType {
     // Literals
     Integer(i64),
+    // An integer literal too large for i64
+    BigInt(BigInt),
     Float(f64),
     String(String),
+    #[allow(dead_code)]
     Boolean(bool),
 
     // Identifiers and Keywords
@@ -14,10 +19,15 @@ pub enum TokenType {
     Const,
     Fn,
     Return,
+    Yield,
+    Spawn,
+    Await,
     If,
     Else,
     For,
     While,
+    Break,
+    Continue,
     In,
     Try,
     Catch,
@@ -26,6 +36,11 @@ pub enum TokenType {
     False,
     Nil,
     Is,
+    Import,
+    Pub,
+    Struct,
+    Impl,
+    Enum,
 
     // Operators
     Plus,     // +
@@ -36,10 +51,13 @@ pub enum TokenType {
     Power,    // **
 
     // Assignment
-    Assign,      // =
-    ColonAssign, // :=
-    PlusAssign,  // +=
-    MinusAssign, // -=
+    Assign,         // =
+    ColonAssign,    // :=
+    PlusAssign,     // +=
+    MinusAssign,    // -=
+    MultiplyAssign, // *=
+    DivideAssign,   // /=
+    ModuloAssign,   // %=
 
     // Comparison
     Equal,        // ==
@@ -63,22 +81,31 @@ pub enum TokenType {
     RightShift, // >>
 
     // Punctuation
-    LeftParen,    // (
-    RightParen,   // )
-    LeftBrace,    // {
-    RightBrace,   // }
-    LeftBracket,  // [
-    RightBracket, // ]
-    Comma,        // ,
-    Semicolon,    // ;
-    Colon,        // :
-    Dot,          // .
-    Arrow,        // ->
-    Pipeline,     // |>
+    LeftParen,        // (
+    RightParen,       // )
+    LeftBrace,        // {
+    RightBrace,       // }
+    LeftBracket,      // [
+    RightBracket,     // ]
+    Comma,            // ,
+    Semicolon,        // ;
+    Colon,            // :
+    Dot,              // .
+    DotDot,           // ..
+    DotDotEqual,      // ..=
+    Ellipsis,         // ...
+    Arrow,            // ->
+    FatArrow,         // =>
+    Pipeline,         // |>
+    Question,         // ?
+    QuestionQuestion, // ??
+    QuestionDot,      // ?.
 
     // String interpolation
-    InterpolationStart, // ${
-    InterpolationEnd,   // }
+    InterpolatedString(Vec<StringSegment>), // "text ${expr} text"
+    InterpolationStart,                     // ${
+    #[allow(dead_code)]
+    InterpolationEnd, // }
 
     // Command execution
     Command(String), // $(command)
@@ -88,17 +115,30 @@ pub enum TokenType {
 
     // Special
     Newline,
-    EOF,
+    Eof,
 
     // Error
     Error(String),
 }
 
+/// A piece of an interpolated string literal.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringSegment {
+    Text(String),
+    /// Raw source of a `${...}` expression and where it starts.
+    Code {
+        source: String,
+        line: usize,
+        column: usize,
+    },
+}
+
 #[derive(Debug, Clone)]
 pub struct Token {
     pub token_type: TokenType,
     pub line: usize,
     pub column: usize,
+    #[allow(dead_code)]
     pub length: usize,
 }
 
@@ -123,7 +163,7 @@ impl Token {
 
     pub fn eof(line: usize, column: usize) -> Self {
         Self {
-            token_type: TokenType::EOF,
+            token_type: TokenType::Eof,
             line,
             column,
             length: 0,
@@ -137,10 +177,15 @@ pub fn keyword_or_identifier(word: &str) -> TokenType {
         "const" => TokenType::Const,
         "fn" => TokenType::Fn,
         "return" => TokenType::Return,
+        "yield" => TokenType::Yield,
+        "spawn" => TokenType::Spawn,
+        "await" => TokenType::Await,
         "if" => TokenType::If,
         "else" => TokenType::Else,
         "for" => TokenType::For,
         "while" => TokenType::While,
+        "break" => TokenType::Break,
+        "continue" => TokenType::Continue,
         "in" => TokenType::In,
         "try" => TokenType::Try,
         "catch" => TokenType::Catch,
@@ -149,6 +194,11 @@ pub fn keyword_or_identifier(word: &str) -> TokenType {
         "false" => TokenType::False,
         "nil" => TokenType::Nil,
         "is" => TokenType::Is,
+        "import" => TokenType::Import,
+        "pub" => TokenType::Pub,
+        "struct" => TokenType::Struct,
+        "impl" => TokenType::Impl,
+        "enum" => TokenType::Enum,
         _ => TokenType::Identifier(word.to_string()),
     }
 }
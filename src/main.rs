@@ -1,4 +1,5 @@
 mod builtins;
+mod checker;
 mod interpreter;
 mod lexer;
 mod parser;
@@ -8,6 +9,7 @@ use clap::{Arg, Command};
 use rustyline::error::ReadlineError;
 use rustyline::DefaultEditor;
 use std::fs;
+use std::path::PathBuf;
 
 use interpreter::Interpreter;
 use lexer::Lexer;
@@ -42,19 +44,47 @@ fn main() -> Result<()> {
                 .help("Show AST output for debugging")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("no-runtime-types")
+                .long("no-runtime-types")
+                .help("Skip checking type annotations while the script runs")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .subcommand(
+            Command::new("check")
+                .about("Type-check a script without running it")
+                .arg(
+                    Arg::new("file")
+                        .help("The script file to check")
+                        .value_name("FILE")
+                        .required(true),
+                ),
+        )
         .get_matches();
 
-    if matches.get_flag("interactive") {
-        run_interactive_mode()
+    let runtime_types = !matches.get_flag("no-runtime-types");
+
+    if let Some(("check", check_matches)) = matches.subcommand() {
+        let file = check_matches
+            .get_one::<String>("file")
+            .expect("file is required");
+        check_file(file)
+    } else if matches.get_flag("interactive") {
+        run_interactive_mode(runtime_types)
     } else if let Some(file) = matches.get_one::<String>("file") {
-        run_file(file, matches.get_flag("tokens"), matches.get_flag("ast"))
+        run_file(
+            file,
+            matches.get_flag("tokens"),
+            matches.get_flag("ast"),
+            runtime_types,
+        )
     } else {
         eprintln!("Usage: shellux [file] or shellux -i");
         std::process::exit(1);
     }
 }
 
-fn run_file(filename: &str, show_tokens: bool, show_ast: bool) -> Result<()> {
+fn run_file(filename: &str, show_tokens: bool, show_ast: bool, runtime_types: bool) -> Result<()> {
     let source = fs::read_to_string(filename)?;
 
     if show_tokens {
@@ -62,13 +92,50 @@ fn run_file(filename: &str, show_tokens: bool, show_ast: bool) -> Result<()> {
     } else if show_ast {
         parse_and_display(&source)?
     } else {
-        execute_source(&source)?
+        execute_source(&source, filename, runtime_types)?
     }
 
     Ok(())
 }
 
-fn run_interactive_mode() -> Result<()> {
+fn check_file(filename: &str) -> Result<()> {
+    let source = fs::read_to_string(filename)?;
+    let mut lexer = Lexer::new(&source);
+    let tokens = lexer.tokenize();
+
+    // Check for lexer errors
+    for token in &tokens {
+        if let lexer::token::TokenType::Error(msg) = &token.token_type {
+            eprintln!(
+                "Lexer error at line {}, column {}: {}",
+                token.line, token.column, msg
+            );
+            std::process::exit(1);
+        }
+    }
+
+    let mut parser = Parser::new(tokens);
+    let program = match parser.parse() {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("Parse error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let errors = checker::check(&program);
+    if errors.is_empty() {
+        println!("{}: no type errors found", filename);
+        return Ok(());
+    }
+
+    for error in &errors {
+        eprintln!("{}:{}: type error: {}", filename, error.line, error.message);
+    }
+    std::process::exit(1);
+}
+
+fn run_interactive_mode(runtime_types: bool) -> Result<()> {
     println!("Shellux v0.1.0 - Interactive Mode");
     println!("Type 'exit' to quit, 'help' for help");
     println!("Use arrow keys to navigate command history");
@@ -77,6 +144,7 @@ fn run_interactive_mode() -> Result<()> {
     let mut rl =
         DefaultEditor::new().map_err(|e| anyhow::anyhow!("Failed to create editor: {}", e))?;
     let mut interpreter = Interpreter::new();
+    interpreter.set_runtime_types(runtime_types);
 
     // Try to load history file
     let history_file = std::env::var("HOME")
@@ -115,8 +183,9 @@ fn run_interactive_mode() -> Result<()> {
                         println!();
                         println!("Built-in Commands:");
                         println!("  echo, print, cd, pwd - Shell built-ins");
-                        println!("  read_file, write_file - File operations");
+                        println!("  read_file, read_lines, write_file - File operations");
                         println!("  input, len, to_string - Utility functions");
+                        println!("  spawn {{ }}, await, await_all, channel - Concurrent tasks");
                         println!();
                         println!("Navigation & Editing:");
                         println!("  ↑/↓ (Up/Down) - Navigate command history");
@@ -203,7 +272,7 @@ fn parse_and_display(source: &str) -> Result<()> {
     Ok(())
 }
 
-fn execute_source(source: &str) -> Result<()> {
+fn execute_source(source: &str, filename: &str, runtime_types: bool) -> Result<()> {
     let mut lexer = Lexer::new(source);
     let tokens = lexer.tokenize();
 
@@ -228,10 +297,17 @@ fn execute_source(source: &str) -> Result<()> {
     };
 
     let mut interpreter = Interpreter::new();
+    interpreter.set_runtime_types(runtime_types);
+    interpreter.set_script_path(PathBuf::from(filename));
     match interpreter.interpret(program) {
-        Ok(_) => {} // Successful execution
+        Ok(_) => {
+            if let Some(code) = interpreter.exit_code() {
+                std::process::exit(code);
+            }
+        }
         Err(e) => {
             eprintln!("Runtime error: {}", e);
+            std::process::exit(1);
         }
     }
 
@@ -267,7 +343,7 @@ fn execute_source_repl(source: &str, interpreter: &mut Interpreter) -> Result<()
             // In REPL mode, show the result if it's not nil
             match value {
                 interpreter::Value::Nil => {} // Don't show nil values
-                _ => println!("=> {}", value.to_string()),
+                _ => println!("=> {}", value),
             }
         }
         Err(e) => {
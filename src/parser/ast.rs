@@ -1,7 +1,12 @@
+use num_bigint::BigInt;
+use std::fmt;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     // Literals
     Integer(i64),
+    // An integer literal outside the i64 range
+    BigInt(BigInt),
     Float(f64),
     String(String),
     Boolean(bool),
@@ -23,25 +28,62 @@ pub enum Expr {
         operand: Box<Expr>,
     },
     
-    // Function calls
+    // Function calls: `callee(args)`, where the callee is any expression
     Call {
-        name: String,
-        args: Vec<Expr>,
+        callee: Box<Expr>,
+        args: Vec<Argument>,
+        line: usize,
+    },
+    
+    // Anonymous functions: `fn(x) { ... }`, `fn(x) expr` or `x => expr`
+    Lambda {
+        parameters: Vec<Parameter>,
+        return_type: Option<Type>,
+        body: Vec<Stmt>,
     },
     
     // Method calls
     MethodCall {
         object: Box<Expr>,
         method: String,
-        args: Vec<Expr>,
+        args: Vec<Argument>,
     },
     
     // Array literals
     Array(Vec<Expr>),
     
+    // Tuples: `a, b` after `return`, or `(a, b)`
+    Tuple(Vec<Expr>),
+    
     // Map literals
     Map(Vec<(Expr, Expr)>),
     
+    // `spawn { ... }`: runs the block concurrently, evaluating to its task
+    Spawn(Vec<Stmt>),
+    
+    // `await task`: waits for a task and evaluates to its block's value
+    Await(Box<Expr>),
+    
+    // `value?`: unwraps `Ok`/`Some`, or returns an `Err`/`None` from the function
+    Propagate(Box<Expr>),
+    
+    // Struct construction: `Server { host: "db", port: 5432 }`
+    StructLiteral {
+        name: String,
+        fields: Vec<(String, Expr)>,
+        line: usize,
+    },
+    
+    // `if` used for its value: the value of the last statement of the branch taken
+    If {
+        condition: Box<Expr>,
+        then_block: Vec<Stmt>,
+        else_block: Option<Vec<Stmt>>,
+    },
+    
+    // Block expressions: `{ ... }` evaluates to its last statement
+    Block(Vec<Stmt>),
+    
     // Array/Map indexing
     Index {
         object: Box<Expr>,
@@ -54,6 +96,12 @@ pub enum Expr {
         field: String,
     },
     
+    // `object?.field`: nil when the object is nil or the map lacks the key
+    OptionalFieldAccess {
+        object: Box<Expr>,
+        field: String,
+    },
+    
     // String interpolation
     Interpolation(Vec<InterpolationPart>),
     
@@ -66,10 +114,12 @@ pub enum Expr {
         right: Box<Expr>,
     },
     
-    // Range
+    // Range: start..end, start..=end, optionally followed by `step n`
     Range {
         start: Box<Expr>,
         end: Box<Expr>,
+        inclusive: bool,
+        step: Option<Box<Expr>>,
     },
 }
 
@@ -100,6 +150,7 @@ pub enum BinaryOperator {
     // Logical
     And,
     Or,
+    NilCoalesce,
     
     // Bitwise
     BitwiseAnd,
@@ -107,6 +158,9 @@ pub enum BinaryOperator {
     BitwiseXor,
     LeftShift,
     RightShift,
+    
+    // Membership: `item in collection`
+    In,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -126,12 +180,14 @@ pub enum Stmt {
         name: String,
         type_annotation: Option<Type>,
         value: Expr,
+        line: usize,
     },
     
     Const {
         name: String,
         type_annotation: Option<Type>,
         value: Expr,
+        line: usize,
     },
     
     // Assignment
@@ -139,6 +195,7 @@ pub enum Stmt {
         target: AssignmentTarget,
         operator: AssignmentOperator,
         value: Expr,
+        line: usize,
     },
     
     // Control flow
@@ -165,9 +222,60 @@ pub enum Stmt {
         parameters: Vec<Parameter>,
         return_type: Option<Type>,
         body: Vec<Stmt>,
+        line: usize,
+    },
+    
+    Return {
+        value: Option<Expr>,
+        line: usize,
+    },
+    
+    // `yield value` hands a value to the consumer; it makes the enclosing
+    // function a generator
+    Yield {
+        value: Expr,
+        line: usize,
+    },
+    
+    // Modules: `import "lib/git.sx" as git` or `from "util.sx" import retry, log`.
+    // Without `as`, a whole-module import is named after the file.
+    Import {
+        path: String,
+        alias: Option<String>,
+        names: Vec<String>,
+        line: usize,
     },
     
-    Return(Option<Expr>),
+    // Record types: `struct Server { host: string, port: int }`
+    Struct {
+        name: String,
+        fields: Vec<(String, Type)>,
+        line: usize,
+    },
+    
+    // Sum types: `enum Shape { Circle(float), Rect(float, float), Empty }`
+    Enum {
+        name: String,
+        variants: Vec<(String, Vec<Type>)>,
+        line: usize,
+    },
+    
+    // Methods for a struct; each is a `Stmt::Function`
+    Impl {
+        name: String,
+        methods: Vec<Stmt>,
+        line: usize,
+    },
+    
+    // `pub` declarations, exported from their module
+    Export(Box<Stmt>),
+    
+    // Destructuring declaration: `a, b := f()`, `[x, ..rest] := items`
+    Destructure {
+        pattern: Pattern,
+        value: Expr,
+        line: usize,
+    },
     
     // Error handling
     Try {
@@ -204,12 +312,30 @@ pub enum AssignmentOperator {
     Assign,
     AddAssign,
     SubtractAssign,
+    MultiplyAssign,
+    DivideAssign,
+    ModuloAssign,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Parameter {
     pub name: String,
     pub type_annotation: Option<Type>,
+    // `name = expr`, evaluated at each call that omits the argument
+    pub default: Option<Expr>,
+    // `...name` collects the remaining positional arguments into an array
+    pub variadic: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Argument {
+    Positional(Expr),
+    
+    // `name: value`
+    Named(String, Expr),
+    
+    // `...array` passes each element as a positional argument
+    Spread(Expr),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -222,6 +348,7 @@ pub struct CatchClause {
 #[derive(Debug, Clone, PartialEq)]
 pub struct MatchArm {
     pub pattern: Pattern,
+    pub guard: Option<Expr>,
     pub body: Vec<Stmt>,
 }
 
@@ -230,6 +357,35 @@ pub enum Pattern {
     Literal(Expr),
     Identifier(String),
     Wildcard,
+    
+    // [first, ..rest]
+    Array(Vec<Pattern>),
+    
+    // `..name` or `..` inside an array pattern
+    Rest(Option<String>),
+    
+    // {"status": s}
+    Map(Vec<(String, Pattern)>),
+    
+    // 200..299, 200..=299
+    Range {
+        start: Expr,
+        end: Expr,
+        inclusive: bool,
+    },
+    
+    // a | b
+    Or(Vec<Pattern>),
+    
+    // a, b (matches tuples and arrays)
+    Tuple(Vec<Pattern>),
+    
+    // Ok(value), None, Shape.Circle(r): a capitalized name is a variant
+    Variant {
+        enum_name: Option<String>,
+        name: String,
+        fields: Vec<Pattern>,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -247,10 +403,34 @@ pub enum Type {
         parameters: Vec<Type>,
         return_type: Box<Type>,
     },
+    Tuple(Vec<Type>),
     Custom(String),
     Any,
 }
 
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Float => write!(f, "float"),
+            Type::String => write!(f, "string"),
+            Type::Bool => write!(f, "bool"),
+            Type::Array(element) => write!(f, "[]{}", element),
+            Type::Map { key_type, value_type } => write!(f, "map[{}]{}", key_type, value_type),
+            Type::Function { parameters, return_type } => {
+                let parameters: Vec<String> = parameters.iter().map(|p| p.to_string()).collect();
+                write!(f, "fn({}) -> {}", parameters.join(", "), return_type)
+            }
+            Type::Tuple(types) => {
+                let types: Vec<String> = types.iter().map(|t| t.to_string()).collect();
+                write!(f, "({})", types.join(", "))
+            }
+            Type::Custom(name) => write!(f, "{}", name),
+            Type::Any => write!(f, "any"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Program {
     pub statements: Vec<Stmt>,
@@ -1,17 +1,23 @@
 pub mod ast;
 
-use crate::lexer::{token::Token, token::TokenType};
+use crate::lexer::{token::StringSegment, token::Token, token::TokenType, Lexer};
 use anyhow::{anyhow, Result};
 use ast::*;
 
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    // Cleared while parsing a match guard, where `x =>` ends the guard
+    allow_arrow_lambda: bool,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, current: 0 }
+        Self {
+            tokens,
+            current: 0,
+            allow_arrow_lambda: true,
+        }
     }
 
     pub fn parse(&mut self) -> Result<Program> {
@@ -40,7 +46,9 @@ impl Parser {
             self.parse_let_statement()
         } else if self.match_keywords(&[TokenType::Const]) {
             self.parse_const_statement()
-        } else if self.match_keywords(&[TokenType::Fn]) {
+        } else if self.check(&TokenType::Fn) && !self.check_next(&TokenType::LeftParen) {
+            // `fn(` starts an anonymous function expression instead
+            self.advance();
             self.parse_function_statement()
         } else if self.match_keywords(&[TokenType::If]) {
             self.parse_if_statement()
@@ -50,14 +58,43 @@ impl Parser {
             self.parse_while_statement()
         } else if self.match_keywords(&[TokenType::Return]) {
             self.parse_return_statement()
+        } else if self.match_keywords(&[TokenType::Yield]) {
+            let line = self.previous_line();
+            let value = self.parse_expression()?;
+            self.consume_newline_or_eof()?;
+            Ok(Stmt::Yield { value, line })
         } else if self.match_keywords(&[TokenType::Try]) {
             self.parse_try_statement()
         } else if self.match_keywords(&[TokenType::Match]) {
             self.parse_match_statement()
+        } else if self.match_keywords(&[TokenType::Struct]) {
+            self.parse_struct_statement()
+        } else if self.match_keywords(&[TokenType::Enum]) {
+            self.parse_enum_statement()
+        } else if self.match_keywords(&[TokenType::Impl]) {
+            self.parse_impl_statement()
+        } else if self.match_keywords(&[TokenType::Import]) {
+            self.parse_import_statement()
+        } else if self.check_identifier("from")
+            && self.check_next(&TokenType::String(String::new()))
+        {
+            // `from` is only special before a module path
+            self.advance();
+            self.parse_from_import_statement()
+        } else if self.match_keywords(&[TokenType::Pub]) {
+            self.parse_pub_statement()
+        } else if self.match_keywords(&[TokenType::Break]) {
+            self.consume_newline_or_eof()?;
+            Ok(Stmt::Break)
+        } else if self.match_keywords(&[TokenType::Continue]) {
+            self.consume_newline_or_eof()?;
+            Ok(Stmt::Continue)
         } else {
             // Check for 'is' assignment (variable declaration)
             if self.is_is_assignment() {
                 self.parse_is_assignment_statement()
+            } else if self.is_destructuring() {
+                self.parse_destructuring_statement()
             } else if self.is_assignment() {
                 self.parse_assignment_statement()
             } else if self.is_command_style_call() {
@@ -73,6 +110,7 @@ impl Parser {
     }
 
     fn parse_let_statement(&mut self) -> Result<Stmt> {
+        let line = self.previous_line();
         let name = self.expect_identifier()?;
 
         let type_annotation = if self.match_token(&TokenType::Colon) {
@@ -89,10 +127,12 @@ impl Parser {
             name,
             type_annotation,
             value,
+            line,
         })
     }
 
     fn parse_const_statement(&mut self) -> Result<Stmt> {
+        let line = self.previous_line();
         let name = self.expect_identifier()?;
 
         let type_annotation = if self.match_token(&TokenType::Colon) {
@@ -109,26 +149,74 @@ impl Parser {
             name,
             type_annotation,
             value,
+            line,
         })
     }
 
     fn parse_function_statement(&mut self) -> Result<Stmt> {
+        let line = self.previous_line();
         let name = self.expect_identifier()?;
         self.expect_token(&TokenType::LeftParen)?;
+        let parameters = self.parse_parameters()?;
+
+        let return_type = if self.match_token(&TokenType::Arrow) {
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+
+        self.expect_token(&TokenType::LeftBrace)?;
+        let body = self.parse_block()?;
+
+        Ok(Stmt::Function {
+            name,
+            parameters,
+            return_type,
+            body,
+            line,
+        })
+    }
 
-        let mut parameters = Vec::new();
+    /// Parses a parameter list after its opening `(`, through the closing `)`.
+    ///
+    /// Parameters with defaults must follow those without, and a `...rest`
+    /// parameter must come last.
+    fn parse_parameters(&mut self) -> Result<Vec<Parameter>> {
+        let mut parameters: Vec<Parameter> = Vec::new();
         if !self.check(&TokenType::RightParen) {
             loop {
+                if let Some(last) = parameters.last().filter(|p| p.variadic) {
+                    return Err(anyhow!(
+                        "Variadic parameter '{}' must be the last parameter",
+                        last.name
+                    ));
+                }
+                let variadic = self.match_token(&TokenType::Ellipsis);
                 let param_name = self.expect_identifier()?;
                 let type_annotation = if self.match_token(&TokenType::Colon) {
                     Some(self.parse_type()?)
                 } else {
                     None
                 };
+                let default = if !variadic && self.match_token(&TokenType::Assign) {
+                    Some(self.parse_expression()?)
+                } else {
+                    None
+                };
+
+                let follows_default = parameters.iter().any(|p| p.default.is_some());
+                if follows_default && default.is_none() && !variadic {
+                    return Err(anyhow!(
+                        "Parameter '{}' without a default follows one with a default",
+                        param_name
+                    ));
+                }
 
                 parameters.push(Parameter {
                     name: param_name,
                     type_annotation,
+                    default,
+                    variadic,
                 });
 
                 if !self.match_token(&TokenType::Comma) {
@@ -138,6 +226,13 @@ impl Parser {
         }
 
         self.expect_token(&TokenType::RightParen)?;
+        Ok(parameters)
+    }
+
+    /// Parses `fn(params) { ... }` or `fn(params) expr` after the `fn`.
+    fn parse_lambda(&mut self) -> Result<Expr> {
+        self.expect_token(&TokenType::LeftParen)?;
+        let parameters = self.parse_parameters()?;
 
         let return_type = if self.match_token(&TokenType::Arrow) {
             Some(self.parse_type()?)
@@ -145,34 +240,104 @@ impl Parser {
             None
         };
 
-        self.expect_token(&TokenType::LeftBrace)?;
-        let body = self.parse_block()?;
-
-        Ok(Stmt::Function {
-            name,
+        let body = self.parse_lambda_body()?;
+        Ok(Expr::Lambda {
             parameters,
             return_type,
             body,
         })
     }
 
+    /// Parses the body of `x => ...` or `(x, y) => ...` after the `=>`.
+    fn parse_arrow_lambda(&mut self, names: Vec<String>) -> Result<Expr> {
+        let parameters = names
+            .into_iter()
+            .map(|name| Parameter {
+                name,
+                type_annotation: None,
+                default: None,
+                variadic: false,
+            })
+            .collect();
+
+        let body = self.parse_lambda_body()?;
+        Ok(Expr::Lambda {
+            parameters,
+            return_type: None,
+            body,
+        })
+    }
+
+    /// A lambda body is either a block or a single expression whose value is
+    /// returned.
+    fn parse_lambda_body(&mut self) -> Result<Vec<Stmt>> {
+        if self.match_token(&TokenType::LeftBrace) {
+            self.parse_block()
+        } else {
+            Ok(vec![Stmt::Expression(self.parse_expression()?)])
+        }
+    }
+
+    /// After an opening `(`, checks for `a, b) =>` and consumes it, returning
+    /// the parameter names. Leaves the position alone otherwise.
+    fn match_arrow_parameters(&mut self) -> Option<Vec<String>> {
+        if !self.allow_arrow_lambda {
+            return None;
+        }
+
+        let start = self.current;
+        let mut names = Vec::new();
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                match self.expect_identifier() {
+                    Ok(name) => names.push(name),
+                    Err(_) => {
+                        self.current = start;
+                        return None;
+                    }
+                }
+                if !self.match_token(&TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+
+        if self.match_token(&TokenType::RightParen) && self.match_token(&TokenType::FatArrow) {
+            Some(names)
+        } else {
+            self.current = start;
+            None
+        }
+    }
+
     fn parse_if_statement(&mut self) -> Result<Stmt> {
+        let (condition, then_block, else_block) = self.parse_if_parts()?;
+        Ok(Stmt::If {
+            condition,
+            then_block,
+            else_block,
+        })
+    }
+
+    /// Parses the rest of an `if` after the keyword. An `else if` chain
+    /// becomes an else block holding a single nested `if`.
+    fn parse_if_parts(&mut self) -> Result<(Expr, Vec<Stmt>, Option<Vec<Stmt>>)> {
         let condition = self.parse_expression()?;
         self.expect_token(&TokenType::LeftBrace)?;
         let then_block = self.parse_block()?;
 
         let else_block = if self.match_keywords(&[TokenType::Else]) {
-            self.expect_token(&TokenType::LeftBrace)?;
-            Some(self.parse_block()?)
+            if self.match_keywords(&[TokenType::If]) {
+                Some(vec![self.parse_if_statement()?])
+            } else {
+                self.expect_token(&TokenType::LeftBrace)?;
+                Some(self.parse_block()?)
+            }
         } else {
             None
         };
 
-        Ok(Stmt::If {
-            condition,
-            then_block,
-            else_block,
-        })
+        Ok((condition, then_block, else_block))
     }
 
     fn parse_for_statement(&mut self) -> Result<Stmt> {
@@ -198,14 +363,158 @@ impl Parser {
     }
 
     fn parse_return_statement(&mut self) -> Result<Stmt> {
-        let value = if self.check(&TokenType::Newline) || self.is_at_end() {
+        let line = self.previous_line();
+        let value = if self.check(&TokenType::Newline)
+            || self.check(&TokenType::RightBrace)
+            || self.is_at_end()
+        {
             None
         } else {
-            Some(self.parse_expression()?)
+            // `return a, b` returns a tuple
+            let first = self.parse_expression()?;
+            if self.check(&TokenType::Comma) {
+                let mut elements = vec![first];
+                while self.match_token(&TokenType::Comma) {
+                    elements.push(self.parse_expression()?);
+                }
+                Some(Expr::Tuple(elements))
+            } else {
+                Some(first)
+            }
+        };
+
+        self.consume_newline_or_eof()?;
+        Ok(Stmt::Return { value, line })
+    }
+
+    fn parse_struct_statement(&mut self) -> Result<Stmt> {
+        let line = self.previous_line();
+        let name = self.expect_identifier()?;
+        self.expect_token(&TokenType::LeftBrace)?;
+
+        // Fields are separated by commas, newlines or both
+        let mut fields = Vec::new();
+        self.skip_newlines();
+        while !self.check(&TokenType::RightBrace) {
+            let field = self.expect_identifier()?;
+            self.expect_token(&TokenType::Colon)?;
+            fields.push((field, self.parse_type()?));
+            self.match_token(&TokenType::Comma);
+            self.skip_newlines();
+        }
+        self.expect_token(&TokenType::RightBrace)?;
+        self.consume_newline_or_eof()?;
+
+        Ok(Stmt::Struct { name, fields, line })
+    }
+
+    fn parse_enum_statement(&mut self) -> Result<Stmt> {
+        let line = self.previous_line();
+        let name = self.expect_identifier()?;
+        self.expect_token(&TokenType::LeftBrace)?;
+
+        // Variants are separated by commas, newlines or both
+        let mut variants = Vec::new();
+        self.skip_newlines();
+        while !self.check(&TokenType::RightBrace) {
+            let variant = self.expect_identifier()?;
+            let mut payload = Vec::new();
+            if self.match_token(&TokenType::LeftParen) {
+                while !self.check(&TokenType::RightParen) {
+                    payload.push(self.parse_type()?);
+                    if !self.match_token(&TokenType::Comma) {
+                        break;
+                    }
+                }
+                self.expect_token(&TokenType::RightParen)?;
+            }
+            variants.push((variant, payload));
+            self.match_token(&TokenType::Comma);
+            self.skip_newlines();
+        }
+        self.expect_token(&TokenType::RightBrace)?;
+        self.consume_newline_or_eof()?;
+
+        Ok(Stmt::Enum {
+            name,
+            variants,
+            line,
+        })
+    }
+
+    fn parse_impl_statement(&mut self) -> Result<Stmt> {
+        let line = self.previous_line();
+        let name = self.expect_identifier()?;
+        self.expect_token(&TokenType::LeftBrace)?;
+
+        let mut methods = Vec::new();
+        self.skip_newlines();
+        while !self.check(&TokenType::RightBrace) {
+            self.expect_token(&TokenType::Fn)?;
+            methods.push(self.parse_function_statement()?);
+            self.skip_newlines();
+        }
+        self.expect_token(&TokenType::RightBrace)?;
+        self.consume_newline_or_eof()?;
+
+        Ok(Stmt::Impl {
+            name,
+            methods,
+            line,
+        })
+    }
+
+    fn parse_import_statement(&mut self) -> Result<Stmt> {
+        let line = self.previous_line();
+        let path = self.expect_string()?;
+        let alias = if self.check_identifier("as") {
+            self.advance();
+            Some(self.expect_identifier()?)
+        } else {
+            None
         };
+        self.consume_newline_or_eof()?;
+
+        Ok(Stmt::Import {
+            path,
+            alias,
+            names: Vec::new(),
+            line,
+        })
+    }
 
+    fn parse_from_import_statement(&mut self) -> Result<Stmt> {
+        let line = self.previous_line();
+        let path = self.expect_string()?;
+        self.expect_token(&TokenType::Import)?;
+        let mut names = vec![self.expect_identifier()?];
+        while self.match_token(&TokenType::Comma) {
+            names.push(self.expect_identifier()?);
+        }
         self.consume_newline_or_eof()?;
-        Ok(Stmt::Return(value))
+
+        Ok(Stmt::Import {
+            path,
+            alias: None,
+            names,
+            line,
+        })
+    }
+
+    fn parse_pub_statement(&mut self) -> Result<Stmt> {
+        let line = self.previous_line();
+        let stmt = self.parse_statement()?;
+        match stmt {
+            Stmt::Function { .. }
+            | Stmt::Let { .. }
+            | Stmt::Const { .. }
+            | Stmt::Struct { .. }
+            | Stmt::Enum { .. } => Ok(Stmt::Export(Box::new(stmt))),
+            _ => Err(anyhow!(
+                "'pub' must be followed by fn, struct, enum, let, const or a := declaration at line {}",
+                line
+            )),
+        }
     }
 
     fn parse_try_statement(&mut self) -> Result<Stmt> {
@@ -214,13 +523,17 @@ impl Parser {
 
         let mut catch_clauses = Vec::new();
         while self.match_keywords(&[TokenType::Catch]) {
-            let exception_type = if let Ok(name) = self.expect_identifier() {
-                Some(name)
+            // `catch`, `catch Kind`, `catch Kind as e` or `catch as e`
+            let exception_type = if self.check(&TokenType::Identifier(String::new()))
+                && !self.check_identifier("as")
+            {
+                Some(self.expect_identifier()?)
             } else {
                 None
             };
 
-            let variable = if self.match_keywords(&[TokenType::Identifier("as".to_string())]) {
+            let variable = if self.check_identifier("as") {
+                self.advance();
                 Some(self.expect_identifier()?)
             } else {
                 None
@@ -247,13 +560,40 @@ impl Parser {
         self.expect_token(&TokenType::LeftBrace)?;
 
         let mut arms = Vec::new();
-        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+        loop {
+            // Arms may be separated by newlines, comments and commas
+            while self.match_token(&TokenType::Newline)
+                || self.match_token(&TokenType::Comma)
+                || self.skip_comment()
+            {}
+            if self.check(&TokenType::RightBrace) || self.is_at_end() {
+                break;
+            }
+
             let pattern = self.parse_pattern()?;
-            self.expect_token(&TokenType::Arrow)?;
-            self.expect_token(&TokenType::LeftBrace)?;
-            let body = self.parse_block()?;
+            let guard = if self.match_keywords(&[TokenType::If]) {
+                self.allow_arrow_lambda = false;
+                let guard = self.parse_expression();
+                self.allow_arrow_lambda = true;
+                Some(guard?)
+            } else {
+                None
+            };
+
+            if !self.match_token(&TokenType::FatArrow) {
+                self.expect_token(&TokenType::Arrow)?;
+            }
+            let body = if self.match_token(&TokenType::LeftBrace) {
+                self.parse_block()?
+            } else {
+                vec![self.parse_statement()?]
+            };
 
-            arms.push(MatchArm { pattern, body });
+            arms.push(MatchArm {
+                pattern,
+                guard,
+                body,
+            });
         }
 
         self.expect_token(&TokenType::RightBrace)?;
@@ -261,6 +601,7 @@ impl Parser {
     }
 
     fn parse_assignment_statement(&mut self) -> Result<Stmt> {
+        let line = self.current_line();
         let target = self.parse_assignment_target()?;
 
         let operator = if self.match_token(&TokenType::Assign) {
@@ -269,6 +610,12 @@ impl Parser {
             AssignmentOperator::AddAssign
         } else if self.match_token(&TokenType::MinusAssign) {
             AssignmentOperator::SubtractAssign
+        } else if self.match_token(&TokenType::MultiplyAssign) {
+            AssignmentOperator::MultiplyAssign
+        } else if self.match_token(&TokenType::DivideAssign) {
+            AssignmentOperator::DivideAssign
+        } else if self.match_token(&TokenType::ModuloAssign) {
+            AssignmentOperator::ModuloAssign
         } else {
             return Err(anyhow!("Expected assignment operator"));
         };
@@ -280,6 +627,7 @@ impl Parser {
             target,
             operator,
             value,
+            line,
         })
     }
 
@@ -302,7 +650,37 @@ impl Parser {
     }
 
     fn parse_expression(&mut self) -> Result<Expr> {
-        self.parse_or()
+        self.parse_pipeline()
+    }
+
+    fn parse_pipeline(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_coalesce()?;
+
+        while self.match_continued(&TokenType::Pipeline) {
+            self.skip_newlines();
+            let right = self.parse_coalesce()?;
+            expr = Expr::Pipeline {
+                left: Box::new(expr),
+                right: Box::new(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_coalesce(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_or()?;
+
+        while self.match_token(&TokenType::QuestionQuestion) {
+            let right = self.parse_or()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                operator: BinaryOperator::NilCoalesce,
+                right: Box::new(right),
+            };
+        }
+
+        Ok(expr)
     }
 
     fn parse_or(&mut self) -> Result<Expr> {
@@ -351,9 +729,98 @@ impl Parser {
     }
 
     fn parse_comparison(&mut self) -> Result<Expr> {
-        let mut expr = self.parse_term()?;
+        let mut expr = self.parse_range()?;
 
         while let Some(op) = self.match_comparison_operator() {
+            let right = self.parse_range()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                operator: op,
+                right: Box::new(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_range(&mut self) -> Result<Expr> {
+        let start = self.parse_bitwise_or()?;
+
+        let inclusive = if self.match_token(&TokenType::DotDotEqual) {
+            true
+        } else if self.match_token(&TokenType::DotDot) {
+            false
+        } else {
+            return Ok(start);
+        };
+
+        let end = self.parse_bitwise_or()?;
+        let step = if self.check_identifier("step") {
+            self.advance();
+            Some(Box::new(self.parse_bitwise_or()?))
+        } else {
+            None
+        };
+
+        Ok(Expr::Range {
+            start: Box::new(start),
+            end: Box::new(end),
+            inclusive,
+            step,
+        })
+    }
+
+    // Bitwise operators bind as in Rust: `|` loosest, then `^`, `&` and the
+    // shifts, all tighter than comparisons and looser than arithmetic
+    fn parse_bitwise_or(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_bitwise_xor()?;
+
+        while self.match_token(&TokenType::BitwiseOr) {
+            let right = self.parse_bitwise_xor()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                operator: BinaryOperator::BitwiseOr,
+                right: Box::new(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_bitwise_xor(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_bitwise_and()?;
+
+        while self.match_token(&TokenType::BitwiseXor) {
+            let right = self.parse_bitwise_and()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                operator: BinaryOperator::BitwiseXor,
+                right: Box::new(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_bitwise_and(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_shift()?;
+
+        while self.match_token(&TokenType::BitwiseAnd) {
+            let right = self.parse_shift()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                operator: BinaryOperator::BitwiseAnd,
+                right: Box::new(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_shift(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_term()?;
+
+        while let Some(op) = self.match_shift_operator() {
             let right = self.parse_term()?;
             expr = Expr::Binary {
                 left: Box::new(expr),
@@ -396,15 +863,68 @@ impl Parser {
     }
 
     fn parse_unary(&mut self) -> Result<Expr> {
-        if let Some(op) = self.match_unary_operator() {
+        if self.match_token(&TokenType::Await) {
+            let task = self.parse_unary()?;
+            Ok(Expr::Await(Box::new(task)))
+        } else if let Some(op) = self.match_unary_operator() {
             let operand = self.parse_unary()?;
             Ok(Expr::Unary {
                 operator: op,
                 operand: Box::new(operand),
             })
         } else {
-            self.parse_postfix()
+            self.parse_power()
+        }
+    }
+
+    /// `**` binds tighter than unary minus on its left (`-2 ** 2` is -4) and
+    /// is right-associative (`2 ** 3 ** 2` is 2 ** 9).
+    fn parse_power(&mut self) -> Result<Expr> {
+        let base = self.parse_postfix()?;
+        if !self.match_token(&TokenType::Power) {
+            return Ok(base);
+        }
+        let exponent = self.parse_unary()?;
+        Ok(Expr::Binary {
+            left: Box::new(base),
+            operator: BinaryOperator::Power,
+            right: Box::new(exponent),
+        })
+    }
+
+    /// Parses call arguments after the opening `(`, through the closing `)`:
+    /// `value`, `name: value` or `...array`. Named arguments come last.
+    fn parse_arguments(&mut self) -> Result<Vec<Argument>> {
+        let mut args = Vec::new();
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                let arg = if self.match_token(&TokenType::Ellipsis) {
+                    Argument::Spread(self.parse_expression()?)
+                } else if self.check(&TokenType::Identifier(String::new()))
+                    && self.check_next(&TokenType::Colon)
+                {
+                    let name = self.expect_identifier()?;
+                    self.advance();
+                    Argument::Named(name, self.parse_expression()?)
+                } else {
+                    Argument::Positional(self.parse_expression()?)
+                };
+                if !matches!(arg, Argument::Named(..))
+                    && matches!(args.last(), Some(Argument::Named(..)))
+                {
+                    return Err(anyhow!(
+                        "Positional argument after named arguments at line {}",
+                        self.previous_line()
+                    ));
+                }
+                args.push(arg);
+                if !self.match_token(&TokenType::Comma) {
+                    break;
+                }
+            }
         }
+        self.expect_token(&TokenType::RightParen)?;
+        Ok(args)
     }
 
     fn parse_postfix(&mut self) -> Result<Expr> {
@@ -413,22 +933,13 @@ impl Parser {
         loop {
             if self.match_token(&TokenType::LeftParen) {
                 // Function call
-                let mut args = Vec::new();
-                if !self.check(&TokenType::RightParen) {
-                    loop {
-                        args.push(self.parse_expression()?);
-                        if !self.match_token(&TokenType::Comma) {
-                            break;
-                        }
-                    }
-                }
-                self.expect_token(&TokenType::RightParen)?;
-
-                if let Expr::Identifier(name) = expr {
-                    expr = Expr::Call { name, args };
-                } else {
-                    return Err(anyhow!("Invalid function call"));
-                }
+                let line = self.previous_line();
+                let args = self.parse_arguments()?;
+                expr = Expr::Call {
+                    callee: Box::new(expr),
+                    args,
+                    line,
+                };
             } else if self.match_token(&TokenType::LeftBracket) {
                 // Array/Map indexing
                 let index = self.parse_expression()?;
@@ -443,17 +954,7 @@ impl Parser {
 
                 if self.match_token(&TokenType::LeftParen) {
                     // Method call
-                    let mut args = Vec::new();
-                    if !self.check(&TokenType::RightParen) {
-                        loop {
-                            args.push(self.parse_expression()?);
-                            if !self.match_token(&TokenType::Comma) {
-                                break;
-                            }
-                        }
-                    }
-                    self.expect_token(&TokenType::RightParen)?;
-
+                    let args = self.parse_arguments()?;
                     expr = Expr::MethodCall {
                         object: Box::new(expr),
                         method: field,
@@ -465,6 +966,14 @@ impl Parser {
                         field,
                     };
                 }
+            } else if self.match_token(&TokenType::QuestionDot) {
+                let field = self.expect_identifier()?;
+                expr = Expr::OptionalFieldAccess {
+                    object: Box::new(expr),
+                    field,
+                };
+            } else if self.match_token(&TokenType::Question) {
+                expr = Expr::Propagate(Box::new(expr));
             } else {
                 break;
             }
@@ -477,16 +986,46 @@ impl Parser {
         if let Some(token) = self.advance() {
             match &token.token_type {
                 TokenType::Integer(n) => Ok(Expr::Integer(*n)),
+                TokenType::BigInt(n) => Ok(Expr::BigInt(n.clone())),
                 TokenType::Float(f) => Ok(Expr::Float(*f)),
                 TokenType::String(s) => Ok(Expr::String(s.clone())),
+                TokenType::InterpolatedString(segments) => {
+                    let segments = segments.clone();
+                    Self::parse_interpolation(segments)
+                }
                 TokenType::Boolean(b) => Ok(Expr::Boolean(*b)),
                 TokenType::True => Ok(Expr::Boolean(true)),
                 TokenType::False => Ok(Expr::Boolean(false)),
                 TokenType::Nil => Ok(Expr::Nil),
-                TokenType::Identifier(name) => Ok(Expr::Identifier(name.clone())),
+                TokenType::Identifier(name) => {
+                    let name = name.clone();
+                    if self.allow_arrow_lambda && self.match_token(&TokenType::FatArrow) {
+                        self.parse_arrow_lambda(vec![name])
+                    } else if self.is_struct_literal(&name) {
+                        self.parse_struct_literal(name)
+                    } else {
+                        Ok(Expr::Identifier(name))
+                    }
+                }
                 TokenType::Command(cmd) => Ok(Expr::Command(cmd.clone())),
+                TokenType::Fn => self.parse_lambda(),
+                TokenType::Spawn => {
+                    self.expect_token(&TokenType::LeftBrace)?;
+                    Ok(Expr::Spawn(self.parse_block()?))
+                }
                 TokenType::LeftParen => {
+                    if let Some(names) = self.match_arrow_parameters() {
+                        return self.parse_arrow_lambda(names);
+                    }
                     let expr = self.parse_expression()?;
+                    if self.check(&TokenType::Comma) {
+                        let mut elements = vec![expr];
+                        while self.match_token(&TokenType::Comma) {
+                            elements.push(self.parse_expression()?);
+                        }
+                        self.expect_token(&TokenType::RightParen)?;
+                        return Ok(Expr::Tuple(elements));
+                    }
                     self.expect_token(&TokenType::RightParen)?;
                     Ok(expr)
                 }
@@ -503,7 +1042,18 @@ impl Parser {
                     self.expect_token(&TokenType::RightBracket)?;
                     Ok(Expr::Array(elements))
                 }
+                TokenType::If => {
+                    let (condition, then_block, else_block) = self.parse_if_parts()?;
+                    Ok(Expr::If {
+                        condition: Box::new(condition),
+                        then_block,
+                        else_block,
+                    })
+                }
                 TokenType::LeftBrace => {
+                    if self.is_block_expression() {
+                        return Ok(Expr::Block(self.parse_block()?));
+                    }
                     let mut pairs = Vec::new();
                     if !self.check(&TokenType::RightBrace) {
                         loop {
@@ -526,16 +1076,264 @@ impl Parser {
         }
     }
 
+    /// Parses each `${...}` segment of an interpolated string as a full
+    /// expression with its own lexer and parser.
+    fn parse_interpolation(segments: Vec<StringSegment>) -> Result<Expr> {
+        let mut parts = Vec::new();
+        for segment in segments {
+            match segment {
+                StringSegment::Text(text) => parts.push(InterpolationPart::Text(text)),
+                StringSegment::Code {
+                    source,
+                    line,
+                    column,
+                } => {
+                    let mut tokens = Lexer::new(&source).tokenize();
+                    // Report lines relative to the enclosing source
+                    for token in &mut tokens {
+                        token.line += line - 1;
+                    }
+                    if let Some(TokenType::Error(msg)) = tokens
+                        .iter()
+                        .map(|t| &t.token_type)
+                        .find(|t| matches!(t, TokenType::Error(_)))
+                    {
+                        return Err(anyhow!(
+                            "{} in interpolation at line {}, column {}",
+                            msg,
+                            line,
+                            column
+                        ));
+                    }
+
+                    let mut parser = Parser::new(tokens);
+                    parser.skip_newlines();
+                    let expr = parser.parse_expression().map_err(|e| {
+                        anyhow!("{} in interpolation at line {}, column {}", e, line, column)
+                    })?;
+                    parser.skip_newlines();
+                    if !parser.is_at_end() {
+                        return Err(anyhow!(
+                            "Unexpected {:?} in interpolation at line {}, column {}",
+                            parser.peek().map(|t| &t.token_type),
+                            line,
+                            column
+                        ));
+                    }
+                    parts.push(InterpolationPart::Expression(Box::new(expr)));
+                }
+            }
+        }
+        Ok(Expr::Interpolation(parts))
+    }
+
     fn parse_pattern(&mut self) -> Result<Pattern> {
-        if self.match_token(&TokenType::Identifier("_".to_string())) {
-            Ok(Pattern::Wildcard)
-        } else if let Ok(expr) = self.parse_expression() {
-            match expr {
-                Expr::Identifier(name) => Ok(Pattern::Identifier(name)),
-                _ => Ok(Pattern::Literal(expr)),
+        let mut alternatives = vec![self.parse_single_pattern()?];
+        while self.match_token(&TokenType::BitwiseOr) {
+            alternatives.push(self.parse_single_pattern()?);
+        }
+
+        if alternatives.len() == 1 {
+            Ok(alternatives.remove(0))
+        } else {
+            Ok(Pattern::Or(alternatives))
+        }
+    }
+
+    /// Parses one element of an array or tuple pattern, which may be a
+    /// `..rest` if `previous` doesn't already contain one.
+    fn parse_element_pattern(&mut self, previous: &[Pattern]) -> Result<Pattern> {
+        if !self.match_token(&TokenType::DotDot) {
+            return self.parse_pattern();
+        }
+        let name = if self.check(&TokenType::Identifier(String::new())) {
+            Some(self.expect_identifier()?)
+        } else {
+            None
+        };
+        if previous.iter().any(|p| matches!(p, Pattern::Rest(_))) {
+            return Err(anyhow!("Only one '..' is allowed in an array pattern"));
+        }
+        Ok(Pattern::Rest(name))
+    }
+
+    /// Parses the left side of a destructuring declaration: a single pattern,
+    /// or comma-separated patterns that form a tuple pattern.
+    fn parse_destructuring_pattern(&mut self) -> Result<Pattern> {
+        let first = self.parse_element_pattern(&[])?;
+        if !self.check(&TokenType::Comma) {
+            return Ok(first);
+        }
+        let mut elements = vec![first];
+        while self.match_token(&TokenType::Comma) {
+            let element = self.parse_element_pattern(&elements)?;
+            elements.push(element);
+        }
+        Ok(Pattern::Tuple(elements))
+    }
+
+    fn parse_destructuring_statement(&mut self) -> Result<Stmt> {
+        let line = self.current_line();
+        let pattern = self.parse_destructuring_pattern()?;
+        if !self.match_token(&TokenType::ColonAssign) {
+            self.expect_token(&TokenType::Is)?;
+        }
+        let value = self.parse_expression()?;
+        self.consume_newline_or_eof()?;
+
+        // `name := value` is an ordinary declaration, even for a capitalized
+        // name that would otherwise be a variant
+        let pattern = match pattern {
+            Pattern::Variant {
+                enum_name: None,
+                name,
+                fields,
+            } if fields.is_empty() => Pattern::Identifier(name),
+            pattern => pattern,
+        };
+        if let Pattern::Identifier(name) = pattern {
+            return Ok(Stmt::Let {
+                name,
+                type_annotation: None,
+                value,
+                line,
+            });
+        }
+        Ok(Stmt::Destructure {
+            pattern,
+            value,
+            line,
+        })
+    }
+
+    /// Parses a pattern that starts with a name: a binding, or a variant
+    /// when the name is capitalized, qualified (`Shape.Circle`) or followed
+    /// by a parenthesized payload.
+    fn parse_identifier_pattern(&mut self, name: String) -> Result<Pattern> {
+        let capitalized = name.starts_with(char::is_uppercase);
+        let (enum_name, name) = if capitalized && self.match_token(&TokenType::Dot) {
+            (Some(name), self.expect_identifier()?)
+        } else {
+            (None, name)
+        };
+
+        let mut fields = Vec::new();
+        if self.match_token(&TokenType::LeftParen) {
+            while !self.check(&TokenType::RightParen) {
+                let field = self.parse_element_pattern(&fields)?;
+                fields.push(field);
+                if !self.match_token(&TokenType::Comma) {
+                    break;
+                }
+            }
+            self.expect_token(&TokenType::RightParen)?;
+        } else if enum_name.is_none() && !capitalized {
+            return Ok(Pattern::Identifier(name));
+        }
+
+        Ok(Pattern::Variant {
+            enum_name,
+            name,
+            fields,
+        })
+    }
+
+    fn parse_single_pattern(&mut self) -> Result<Pattern> {
+        if self.check_identifier("_") {
+            self.advance();
+            return Ok(Pattern::Wildcard);
+        }
+
+        if self.match_token(&TokenType::LeftBracket) {
+            let mut elements = Vec::new();
+            self.skip_newlines();
+            while !self.check(&TokenType::RightBracket) {
+                let element = self.parse_element_pattern(&elements)?;
+                elements.push(element);
+                self.skip_newlines();
+                if !self.match_token(&TokenType::Comma) {
+                    break;
+                }
+                self.skip_newlines();
+            }
+            self.expect_token(&TokenType::RightBracket)?;
+            return Ok(Pattern::Array(elements));
+        }
+
+        if self.match_token(&TokenType::LeftBrace) {
+            let mut entries = Vec::new();
+            self.skip_newlines();
+            while !self.check(&TokenType::RightBrace) {
+                let entry = match self.advance().map(|t| t.token_type.clone()) {
+                    Some(TokenType::String(key)) => {
+                        self.expect_token(&TokenType::Colon)?;
+                        (key, self.parse_pattern()?)
+                    }
+                    Some(TokenType::Identifier(key)) => {
+                        if self.match_token(&TokenType::Colon) {
+                            (key, self.parse_pattern()?)
+                        } else {
+                            // `{name}` is shorthand for `{"name": name}`
+                            (key.clone(), Pattern::Identifier(key))
+                        }
+                    }
+                    other => return Err(anyhow!("Expected map pattern key, found {:?}", other)),
+                };
+                entries.push(entry);
+                self.skip_newlines();
+                if !self.match_token(&TokenType::Comma) {
+                    break;
+                }
+                self.skip_newlines();
             }
+            self.expect_token(&TokenType::RightBrace)?;
+            return Ok(Pattern::Map(entries));
+        }
+
+        if let Some(TokenType::Identifier(name)) = self.peek().map(|t| t.token_type.clone()) {
+            self.advance();
+            return self.parse_identifier_pattern(name);
+        }
+
+        let start = self.parse_literal_pattern()?;
+        let inclusive = if self.match_token(&TokenType::DotDotEqual) {
+            true
+        } else if self.match_token(&TokenType::DotDot) {
+            false
         } else {
-            Err(anyhow!("Expected pattern"))
+            return Ok(Pattern::Literal(start));
+        };
+        let end = self.parse_literal_pattern()?;
+        Ok(Pattern::Range {
+            start,
+            end,
+            inclusive,
+        })
+    }
+
+    fn parse_literal_pattern(&mut self) -> Result<Expr> {
+        let negative = self.match_token(&TokenType::Minus);
+        let token_type = match self.advance() {
+            Some(token) => token.token_type.clone(),
+            None => return Err(anyhow!("Expected pattern, found end of input")),
+        };
+
+        match (token_type, negative) {
+            (TokenType::Integer(n), _) => Ok(Expr::Integer(if negative { -n } else { n })),
+            // `-9223372036854775808` is the one negated big literal that fits
+            (TokenType::BigInt(n), _) => {
+                let n = if negative { -n } else { n };
+                Ok(match i64::try_from(&n) {
+                    Ok(i) => Expr::Integer(i),
+                    Err(_) => Expr::BigInt(n),
+                })
+            }
+            (TokenType::Float(f), _) => Ok(Expr::Float(if negative { -f } else { f })),
+            (TokenType::String(s), false) => Ok(Expr::String(s)),
+            (TokenType::True, false) => Ok(Expr::Boolean(true)),
+            (TokenType::False, false) => Ok(Expr::Boolean(false)),
+            (TokenType::Nil, false) => Ok(Expr::Nil),
+            (other, _) => Err(anyhow!("Expected pattern, found {:?}", other)),
         }
     }
 
@@ -551,7 +1349,58 @@ impl Parser {
         }
     }
 
+    /// Parses a type annotation: a named type, `[]T`, `map[K]V`,
+    /// `fn(A, B) -> R` or a tuple `(A, B)`.
     fn parse_type(&mut self) -> Result<Type> {
+        if self.match_token(&TokenType::LeftParen) {
+            let mut types = vec![self.parse_type()?];
+            while self.match_token(&TokenType::Comma) {
+                types.push(self.parse_type()?);
+            }
+            self.expect_token(&TokenType::RightParen)?;
+            return Ok(Type::Tuple(types));
+        }
+
+        if self.match_token(&TokenType::LeftBracket) {
+            self.expect_token(&TokenType::RightBracket)?;
+            return Ok(Type::Array(Box::new(self.parse_type()?)));
+        }
+
+        if self.match_token(&TokenType::Fn) {
+            self.expect_token(&TokenType::LeftParen)?;
+            let mut parameters = Vec::new();
+            if !self.check(&TokenType::RightParen) {
+                loop {
+                    parameters.push(self.parse_type()?);
+                    if !self.match_token(&TokenType::Comma) {
+                        break;
+                    }
+                }
+            }
+            self.expect_token(&TokenType::RightParen)?;
+            let return_type = if self.match_token(&TokenType::Arrow) {
+                self.parse_type()?
+            } else {
+                Type::Any
+            };
+            return Ok(Type::Function {
+                parameters,
+                return_type: Box::new(return_type),
+            });
+        }
+
+        if self.check_identifier("map") && self.check_next(&TokenType::LeftBracket) {
+            self.advance();
+            self.advance();
+            let key_type = self.parse_type()?;
+            self.expect_token(&TokenType::RightBracket)?;
+            let value_type = self.parse_type()?;
+            return Ok(Type::Map {
+                key_type: Box::new(key_type),
+                value_type: Box::new(value_type),
+            });
+        }
+
         if let Some(token) = self.advance() {
             match &token.token_type {
                 TokenType::Identifier(name) => match name.as_str() {
@@ -588,6 +1437,7 @@ impl Parser {
     }
 
     fn parse_is_assignment_statement(&mut self) -> Result<Stmt> {
+        let line = self.current_line();
         let name = self.expect_identifier()?;
         self.expect_token(&TokenType::Is)?;
         let value = self.parse_expression()?;
@@ -597,9 +1447,22 @@ impl Parser {
             name,
             type_annotation: None,
             value,
+            line,
         })
     }
 
+    /// Checks for a declaration with `:=`, or a destructuring one with `is`.
+    fn is_destructuring(&mut self) -> bool {
+        let start = self.current;
+        let result = self.parse_destructuring_pattern().is_ok()
+            && matches!(
+                self.peek().map(|t| &t.token_type),
+                Some(TokenType::ColonAssign) | Some(TokenType::Is)
+            );
+        self.current = start; // Reset position
+        result
+    }
+
     fn is_assignment(&mut self) -> bool {
         let start = self.current;
 
@@ -610,6 +1473,9 @@ impl Parser {
                 Some(TokenType::Assign)
                     | Some(TokenType::PlusAssign)
                     | Some(TokenType::MinusAssign)
+                    | Some(TokenType::MultiplyAssign)
+                    | Some(TokenType::DivideAssign)
+                    | Some(TokenType::ModuloAssign)
             );
             self.current = start; // Reset position
             result
@@ -664,6 +1530,23 @@ impl Parser {
                 TokenType::LessEqual => Some(BinaryOperator::LessEqual),
                 TokenType::Greater => Some(BinaryOperator::Greater),
                 TokenType::GreaterEqual => Some(BinaryOperator::GreaterEqual),
+                TokenType::In => Some(BinaryOperator::In),
+                _ => None,
+            };
+            if op.is_some() {
+                self.advance();
+            }
+            op
+        } else {
+            None
+        }
+    }
+
+    fn match_shift_operator(&mut self) -> Option<BinaryOperator> {
+        if let Some(token) = self.peek() {
+            let op = match &token.token_type {
+                TokenType::LeftShift => Some(BinaryOperator::LeftShift),
+                TokenType::RightShift => Some(BinaryOperator::RightShift),
                 _ => None,
             };
             if op.is_some() {
@@ -697,7 +1580,6 @@ impl Parser {
                 TokenType::Multiply => Some(BinaryOperator::Multiply),
                 TokenType::Divide => Some(BinaryOperator::Divide),
                 TokenType::Modulo => Some(BinaryOperator::Modulo),
-                TokenType::Power => Some(BinaryOperator::Power),
                 _ => None,
             };
             if op.is_some() {
@@ -753,6 +1635,17 @@ impl Parser {
         }
     }
 
+    fn expect_string(&mut self) -> Result<String> {
+        if let Some(token) = self.advance() {
+            match &token.token_type {
+                TokenType::String(s) => Ok(s.clone()),
+                _ => Err(anyhow!("Expected string, found {:?}", token.token_type)),
+            }
+        } else {
+            Err(anyhow!("Expected string, found end of input"))
+        }
+    }
+
     fn consume_newline_or_eof(&mut self) -> Result<()> {
         // Skip any newlines
         while self.match_token(&TokenType::Newline) || self.skip_comment() {
@@ -771,6 +1664,90 @@ impl Parser {
         }
     }
 
+    fn skip_newlines(&mut self) {
+        while self.match_token(&TokenType::Newline) || self.skip_comment() {}
+    }
+
+    /// Matches `token_type` even when it starts the next line, so chains like
+    /// pipelines can continue across line breaks. Leaves the newlines in place
+    /// when the token isn't there.
+    fn match_continued(&mut self, token_type: &TokenType) -> bool {
+        let saved = self.current;
+        self.skip_newlines();
+        if self.match_token(token_type) {
+            true
+        } else {
+            self.current = saved;
+            false
+        }
+    }
+
+    /// Line of the next token to be consumed.
+    fn current_line(&self) -> usize {
+        self.peek()
+            .map_or_else(|| self.previous_line(), |token| token.line)
+    }
+
+    /// Line of the most recently consumed token.
+    fn previous_line(&self) -> usize {
+        self.tokens
+            .get(self.current.saturating_sub(1))
+            .map_or(1, |token| token.line)
+    }
+
+    /// `Name {` starts a struct literal when the name is capitalized and the
+    /// brace is followed by `field:` or closes at once, so a condition like
+    /// `if count == limit {` still opens a block.
+    fn is_struct_literal(&self, name: &str) -> bool {
+        if !name.starts_with(char::is_uppercase) || !self.check(&TokenType::LeftBrace) {
+            return false;
+        }
+        let mut tokens = self.tokens[self.current + 1..]
+            .iter()
+            .map(|token| &token.token_type)
+            .skip_while(|t| matches!(t, TokenType::Newline | TokenType::Comment(_)));
+        match tokens.next() {
+            Some(TokenType::RightBrace) => true,
+            Some(TokenType::Identifier(_)) => matches!(tokens.next(), Some(TokenType::Colon)),
+            _ => false,
+        }
+    }
+
+    fn parse_struct_literal(&mut self, name: String) -> Result<Expr> {
+        let line = self.previous_line();
+        self.expect_token(&TokenType::LeftBrace)?;
+        let mut fields = Vec::new();
+        self.skip_newlines();
+        while !self.check(&TokenType::RightBrace) {
+            let field = self.expect_identifier()?;
+            self.expect_token(&TokenType::Colon)?;
+            fields.push((field, self.parse_expression()?));
+            self.match_token(&TokenType::Comma);
+            self.skip_newlines();
+        }
+        self.expect_token(&TokenType::RightBrace)?;
+        Ok(Expr::StructLiteral { name, fields, line })
+    }
+
+    /// After a `{` in expression position, tells a block from a map literal:
+    /// maps are empty or start with `key:` on the same line.
+    fn is_block_expression(&self) -> bool {
+        !self.check(&TokenType::RightBrace) && !self.check_next(&TokenType::Colon)
+    }
+
+    fn check_next(&self, token_type: &TokenType) -> bool {
+        self.tokens.get(self.current + 1).is_some_and(|token| {
+            std::mem::discriminant(&token.token_type) == std::mem::discriminant(token_type)
+        })
+    }
+
+    fn check_identifier(&self, name: &str) -> bool {
+        matches!(
+            self.peek().map(|t| &t.token_type),
+            Some(TokenType::Identifier(n)) if n == name
+        )
+    }
+
     fn peek(&self) -> Option<&Token> {
         self.tokens.get(self.current)
     }
@@ -789,7 +1766,7 @@ impl Parser {
         self.current >= self.tokens.len()
             || self
                 .peek()
-                .map_or(true, |t| matches!(t.token_type, TokenType::EOF))
+                .is_none_or(|t| matches!(t.token_type, TokenType::Eof))
     }
 
     fn skip_comment(&mut self) -> bool {
@@ -809,11 +1786,19 @@ impl Parser {
         if let Some(token) = self.peek() {
             if matches!(token.token_type, TokenType::Identifier(_)) {
                 self.advance();
-                // Check if the next token is not a newline, EOF, or left paren
+                // Check if the next token can start an argument (so `x + y` stays an expression)
                 let next_is_arg = if let Some(next) = self.peek() {
-                    !matches!(
+                    matches!(
                         next.token_type,
-                        TokenType::Newline | TokenType::EOF | TokenType::LeftParen
+                        TokenType::Identifier(_)
+                            | TokenType::Integer(_)
+                            | TokenType::BigInt(_)
+                            | TokenType::Float(_)
+                            | TokenType::String(_)
+                            | TokenType::True
+                            | TokenType::False
+                            | TokenType::Nil
+                            | TokenType::Command(_)
                     )
                 } else {
                     false
@@ -827,17 +1812,22 @@ impl Parser {
     }
 
     fn parse_command_style_call(&mut self) -> Result<Stmt> {
+        let line = self.current_line();
         let name = self.expect_identifier()?;
         let mut args = Vec::new();
 
         // Parse arguments until we hit a newline or EOF
         while !self.check(&TokenType::Newline) && !self.is_at_end() {
-            args.push(self.parse_primary()?);
+            args.push(Argument::Positional(self.parse_primary()?));
         }
 
         self.consume_newline_or_eof()?;
 
-        Ok(Stmt::Expression(Expr::Call { name, args }))
+        Ok(Stmt::Expression(Expr::Call {
+            callee: Box::new(Expr::Identifier(name)),
+            args,
+            line,
+        }))
     }
 }
 
@@ -874,11 +1864,458 @@ mod tests {
 
         assert_eq!(program.statements.len(), 1);
         match &program.statements[0] {
-            Stmt::Expression(Expr::Call { name, args }) => {
-                assert_eq!(name, "print");
+            Stmt::Expression(Expr::Call { callee, args, .. }) => {
+                assert_eq!(**callee, Expr::Identifier("print".to_string()));
                 assert_eq!(args.len(), 1);
             }
             _ => panic!("Expected function call"),
         }
     }
+
+    #[test]
+    fn test_parse_match_patterns() {
+        let source = r#"
+            match value {
+                [first, ..rest] if first > 0 => first
+                {"status": s} | 0..=9 -> { s }
+            }
+        "#;
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        match &program.statements[0] {
+            Stmt::Match { arms, .. } => {
+                assert_eq!(arms.len(), 2);
+                assert_eq!(
+                    arms[0].pattern,
+                    Pattern::Array(vec![
+                        Pattern::Identifier("first".to_string()),
+                        Pattern::Rest(Some("rest".to_string())),
+                    ])
+                );
+                assert!(arms[0].guard.is_some());
+                assert!(matches!(&arms[1].pattern, Pattern::Or(alts) if alts.len() == 2));
+            }
+            _ => panic!("Expected match statement"),
+        }
+    }
+
+    #[test]
+    fn test_parse_interpolated_string() {
+        let source = r#"msg is "sum: ${a + b}!""#;
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        match &program.statements[0] {
+            Stmt::Let {
+                value: Expr::Interpolation(parts),
+                ..
+            } => {
+                assert_eq!(parts.len(), 3);
+                assert_eq!(parts[0], InterpolationPart::Text("sum: ".to_string()));
+                assert!(matches!(
+                    &parts[1],
+                    InterpolationPart::Expression(expr) if matches!(**expr, Expr::Binary { .. })
+                ));
+                assert_eq!(parts[2], InterpolationPart::Text("!".to_string()));
+            }
+            _ => panic!("Expected interpolated let"),
+        }
+    }
+
+    #[test]
+    fn test_parse_multiline_pipeline() {
+        let source = "data |> trim()\n    |> split(\",\")\nnext";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        assert_eq!(program.statements.len(), 2);
+        match &program.statements[0] {
+            Stmt::Expression(Expr::Pipeline { left, right }) => {
+                assert!(matches!(**left, Expr::Pipeline { .. }));
+                assert!(matches!(
+                    &**right,
+                    Expr::Call { callee, .. } if **callee == Expr::Identifier("split".to_string())
+                ));
+            }
+            other => panic!("Expected pipeline, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_lambda_forms() {
+        let source = "f is fn(x) { x * 2 }\ng is (a, b) => a + b\nh is n => n\nk is (1)";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        assert_eq!(program.statements.len(), 4);
+        let values: Vec<&Expr> = program
+            .statements
+            .iter()
+            .map(|stmt| match stmt {
+                Stmt::Let { value, .. } => value,
+                other => panic!("Expected let, got {:?}", other),
+            })
+            .collect();
+        assert!(matches!(values[0], Expr::Lambda { parameters, .. } if parameters.len() == 1));
+        assert!(matches!(values[1], Expr::Lambda { parameters, .. } if parameters.len() == 2));
+        assert!(matches!(values[2], Expr::Lambda { parameters, .. } if parameters.len() == 1));
+        assert_eq!(*values[3], Expr::Integer(1));
+    }
+
+    #[test]
+    fn test_parse_tuple_return_and_destructuring() {
+        let source = "fn pair() -> (int, string) {\n    return 1, \"a\"\n}\nn, s := pair()\nm := 2";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        assert_eq!(program.statements.len(), 3);
+        match &program.statements[0] {
+            Stmt::Function {
+                return_type, body, ..
+            } => {
+                assert_eq!(
+                    *return_type,
+                    Some(Type::Tuple(vec![Type::Int, Type::String]))
+                );
+                assert!(matches!(
+                    &body[0],
+                    Stmt::Return { value: Some(Expr::Tuple(elements)), .. } if elements.len() == 2
+                ));
+            }
+            other => panic!("Expected function, got {:?}", other),
+        }
+        assert!(matches!(
+            &program.statements[1],
+            Stmt::Destructure { pattern: Pattern::Tuple(elements), .. } if elements.len() == 2
+        ));
+        assert!(matches!(&program.statements[2], Stmt::Let { name, .. } if name == "m"));
+    }
+
+    #[test]
+    fn test_parse_else_if_and_if_expression() {
+        let source = "if a {\n    1\n} else if b {\n    2\n} else {\n    3\n}\nx := if a { 1 } else { 2 }\ny := { 1 }\nz := {\"k\": 1}";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        assert_eq!(program.statements.len(), 4);
+        match &program.statements[0] {
+            Stmt::If {
+                else_block: Some(else_block),
+                ..
+            } => assert!(matches!(
+                &else_block[..],
+                [Stmt::If {
+                    else_block: Some(_),
+                    ..
+                }]
+            )),
+            other => panic!("Expected if, got {:?}", other),
+        }
+        assert!(matches!(
+            &program.statements[1],
+            Stmt::Let {
+                value: Expr::If { .. },
+                ..
+            }
+        ));
+        assert!(matches!(
+            &program.statements[2],
+            Stmt::Let { value: Expr::Block(statements), .. } if statements.len() == 1
+        ));
+        assert!(matches!(
+            &program.statements[3],
+            Stmt::Let {
+                value: Expr::Map(_),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_parse_imports_and_pub() {
+        let source = "import \"lib/git.sx\" as git\nfrom \"util.sx\" import retry, log\npub fn f() {\n}\nfrom := 1";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        assert_eq!(program.statements.len(), 4);
+        assert!(matches!(
+            &program.statements[0],
+            Stmt::Import { path, alias: Some(alias), names, .. }
+                if path == "lib/git.sx" && alias == "git" && names.is_empty()
+        ));
+        assert!(matches!(
+            &program.statements[1],
+            Stmt::Import { path, alias: None, names, .. }
+                if path == "util.sx" && names == &["retry", "log"]
+        ));
+        assert!(matches!(
+            &program.statements[2],
+            Stmt::Export(stmt) if matches!(**stmt, Stmt::Function { .. })
+        ));
+        // `from` is still an ordinary name elsewhere
+        assert!(matches!(&program.statements[3], Stmt::Let { name, .. } if name == "from"));
+
+        let mut parser = Parser::new(Lexer::new("pub print(1)").tokenize());
+        assert!(parser.parse().is_err());
+    }
+
+    #[test]
+    fn test_parse_structs() {
+        let source = "struct Server {\n    host: string,\n    port: int\n}\nimpl Server {\n    fn url(self) {\n        return self.host\n    }\n}\ns := Server { host: \"db\", port: 22 }\nif port == Limit {\n    1\n}";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        assert_eq!(program.statements.len(), 4);
+        assert_eq!(
+            program.statements[0],
+            Stmt::Struct {
+                name: "Server".to_string(),
+                fields: vec![
+                    ("host".to_string(), Type::String),
+                    ("port".to_string(), Type::Int),
+                ],
+                line: 1,
+            }
+        );
+        assert!(matches!(
+            &program.statements[1],
+            Stmt::Impl { name, methods, .. } if name == "Server" && methods.len() == 1
+        ));
+        assert!(matches!(
+            &program.statements[2],
+            Stmt::Let { value: Expr::StructLiteral { name, fields, .. }, .. }
+                if name == "Server" && fields.len() == 2
+        ));
+        // A capitalized name before a block is not a struct literal
+        assert!(matches!(&program.statements[3], Stmt::If { .. }));
+    }
+
+    #[test]
+    fn test_parse_enums_and_variant_patterns() {
+        let source = "enum Shape {\n    Circle(float),\n    Empty\n}\nmatch s {\n    Circle(r) => r\n    Shape.Empty => 0\n    None => 1\n    other => 2\n}\nn := parse(x)?\nLimit := 3";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        assert_eq!(program.statements.len(), 4);
+        assert_eq!(
+            program.statements[0],
+            Stmt::Enum {
+                name: "Shape".to_string(),
+                variants: vec![
+                    ("Circle".to_string(), vec![Type::Float]),
+                    ("Empty".to_string(), vec![]),
+                ],
+                line: 1,
+            }
+        );
+        let Stmt::Match { arms, .. } = &program.statements[1] else {
+            panic!("Expected match, got {:?}", program.statements[1]);
+        };
+        let variant = |enum_name: Option<&str>, name: &str, fields| Pattern::Variant {
+            enum_name: enum_name.map(str::to_string),
+            name: name.to_string(),
+            fields,
+        };
+        assert_eq!(
+            arms.iter()
+                .map(|arm| arm.pattern.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                variant(None, "Circle", vec![Pattern::Identifier("r".to_string())]),
+                variant(Some("Shape"), "Empty", vec![]),
+                variant(None, "None", vec![]),
+                Pattern::Identifier("other".to_string()),
+            ]
+        );
+        assert!(matches!(
+            &program.statements[2],
+            Stmt::Let {
+                value: Expr::Propagate(_),
+                ..
+            }
+        ));
+        // A capitalized name on its own is still declared, not matched
+        assert!(matches!(&program.statements[3], Stmt::Let { name, .. } if name == "Limit"));
+    }
+
+    #[test]
+    fn test_parse_nil_coalescing_precedence() {
+        let mut parser = Parser::new(Lexer::new("a?.b ?? c || d").tokenize());
+        let program = parser.parse().unwrap();
+
+        let Stmt::Expression(Expr::Binary {
+            left,
+            operator: BinaryOperator::NilCoalesce,
+            right,
+        }) = &program.statements[0]
+        else {
+            panic!("Expected ??, got {:?}", program.statements[0]);
+        };
+        assert!(matches!(**left, Expr::OptionalFieldAccess { ref field, .. } if field == "b"));
+        assert!(matches!(
+            **right,
+            Expr::Binary {
+                operator: BinaryOperator::Or,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_parse_power_and_bitwise_precedence() {
+        let mut parser =
+            Parser::new(Lexer::new("-2 ** 3 ** 2\na | b ^ c & d << 1\nx in xs == true").tokenize());
+        let program = parser.parse().unwrap();
+
+        // Unary minus applies to the whole right-associative power
+        let Stmt::Expression(Expr::Unary { operand, .. }) = &program.statements[0] else {
+            panic!("Expected unary minus, got {:?}", program.statements[0]);
+        };
+        let Expr::Binary {
+            operator: BinaryOperator::Power,
+            right,
+            ..
+        } = &**operand
+        else {
+            panic!("Expected **, got {:?}", operand);
+        };
+        assert!(matches!(
+            **right,
+            Expr::Binary {
+                operator: BinaryOperator::Power,
+                ..
+            }
+        ));
+
+        let Stmt::Expression(Expr::Binary {
+            operator: BinaryOperator::BitwiseOr,
+            right,
+            ..
+        }) = &program.statements[1]
+        else {
+            panic!("Expected |, got {:?}", program.statements[1]);
+        };
+        let Expr::Binary {
+            operator: BinaryOperator::BitwiseXor,
+            right,
+            ..
+        } = &**right
+        else {
+            panic!("Expected ^, got {:?}", right);
+        };
+        let Expr::Binary {
+            operator: BinaryOperator::BitwiseAnd,
+            right,
+            ..
+        } = &**right
+        else {
+            panic!("Expected &, got {:?}", right);
+        };
+        assert!(matches!(
+            **right,
+            Expr::Binary {
+                operator: BinaryOperator::LeftShift,
+                ..
+            }
+        ));
+
+        assert!(matches!(
+            &program.statements[2],
+            Stmt::Expression(Expr::Binary {
+                operator: BinaryOperator::Equal,
+                left,
+                ..
+            }) if matches!(**left, Expr::Binary { operator: BinaryOperator::In, .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_defaults_named_arguments_and_spread() {
+        let source = "fn deploy(env: string, dry_run: bool = false, ...targets) {\n}\ndeploy(\"prod\", ...hosts, dry_run: true)";
+        let program = Parser::new(Lexer::new(source).tokenize()).parse().unwrap();
+
+        let Stmt::Function { parameters, .. } = &program.statements[0] else {
+            panic!("Expected function, got {:?}", program.statements[0]);
+        };
+        assert_eq!(parameters[1].default, Some(Expr::Boolean(false)));
+        assert!(!parameters[1].variadic);
+        assert!(parameters[2].variadic && parameters[2].default.is_none());
+
+        let Stmt::Expression(Expr::Call { args, .. }) = &program.statements[1] else {
+            panic!("Expected call, got {:?}", program.statements[1]);
+        };
+        assert_eq!(
+            args,
+            &vec![
+                Argument::Positional(Expr::String("prod".to_string())),
+                Argument::Spread(Expr::Identifier("hosts".to_string())),
+                Argument::Named("dry_run".to_string(), Expr::Boolean(true)),
+            ]
+        );
+
+        for source in ["fn f(a = 1, b) {\n}", "fn f(...a, b) {\n}", "f(a: 1, 2)"] {
+            assert!(
+                Parser::new(Lexer::new(source).tokenize()).parse().is_err(),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_yield() {
+        let source = "fn count() {\n    yield 1\n    yield\n}";
+        assert!(Parser::new(Lexer::new(source).tokenize()).parse().is_err());
+
+        let source = "fn count() {\n    yield 1 + 2\n}";
+        let program = Parser::new(Lexer::new(source).tokenize()).parse().unwrap();
+        let Stmt::Function { body, .. } = &program.statements[0] else {
+            panic!("Expected function, got {:?}", program.statements[0]);
+        };
+        assert!(matches!(
+            &body[0],
+            Stmt::Yield {
+                value: Expr::Binary { .. },
+                line: 2
+            }
+        ));
+    }
+
+    #[test]
+    fn test_parse_spawn_and_await() {
+        let source = "task := spawn {\n    ping()\n}\nawait task.result";
+        let program = Parser::new(Lexer::new(source).tokenize()).parse().unwrap();
+
+        assert!(matches!(
+            &program.statements[0],
+            Stmt::Let { value: Expr::Spawn(body), .. } if body.len() == 1
+        ));
+        let Stmt::Expression(Expr::Await(task)) = &program.statements[1] else {
+            panic!("Expected await, got {:?}", program.statements[1]);
+        };
+        assert!(matches!(**task, Expr::FieldAccess { .. }));
+
+        assert!(Parser::new(Lexer::new("spawn ping()").tokenize())
+            .parse()
+            .is_err());
+    }
 }